@@ -1,20 +1,32 @@
-use aws_sdk_cloudwatchlogs::types::LogGroup;
+use futures::Future;
 use lambda_runtime::{Error as LambdaRuntimeError, LambdaEvent};
 use log::{debug, error, info, trace};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use terraform_aws_default_log_retention::global::initialize_metrics;
 use terraform_aws_default_log_retention::{
-    cloudwatch_logs_traits::{DescribeLogGroups, ListTagsForResource, PutRetentionPolicy, TagResource},
+    cloudwatch_logs_traits::{CloudWatchLogs, DescribeLogGroups, ListTagsForResource, PutRetentionPolicy, TagResource},
+    config::{self, Config, ConfigSource},
+    cross_account::{assume_role, role_arn},
     error::{Error, Severity},
-    global::{cloudwatch_logs, initialize_logger, log_group_tags, retention},
+    global::{cloudwatch_logs, cloudwatch_logs_for, initialize_logger, s3, ssm, sts},
     metric_publisher::{self, Metric, MetricName},
+    policy_parameter::PolicyStore,
+    rate_limiter::RateLimitedCloudWatchLogs,
+    report::{LogReportSink, Reporter, S3ReportSink, WriteReport},
+    retention_decision::DecisionPolicy,
+    ssm_traits::Ssm,
+    sweep::{process_all_log_groups, Sweep, SweepTarget},
 };
 
-#[derive(Debug, PartialEq, Eq)]
-enum UpdateResult {
-    AlreadyHasRetention,
-    AlreadyTaggedWithRetention,
-    Updated,
+/// A target which has not been swept to completion, returned so that the next invocation can resume it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PendingTarget {
+    #[serde(flatten)]
+    target: SweepTarget,
+    #[serde(default)]
+    next_token: Option<String>,
 }
 
 #[tokio::main]
@@ -23,10 +35,26 @@ enum UpdateResult {
 async fn main() -> Result<(), LambdaRuntimeError> {
     initialize_logger();
 
-    let metrics = initialize_metrics();
+    // Fail at cold start rather than on every invocation if the configuration is invalid
+    let policy_store = match ConfigSource::load() {
+        Ok(source) => PolicyStore::load(source, ssm().await).await,
+        Err(error) => Err(vec![error]),
+    };
+    let policy_store = match policy_store {
+        Ok(policy_store) => policy_store,
+        Err(errors) => {
+            for error in &errors {
+                error!("Invalid configuration: {}", error);
+            }
+            return Err(config::combine(errors).into());
+        }
+    };
+
+    let metrics = initialize_metrics(&policy_store.config().await?.metric_namespace);
 
     trace!("Getting runtime result...");
-    let result = metrics_cloudwatch_embedded::lambda::handler::run(metrics, func).await;
+    let policy_store = &policy_store;
+    let result = metrics_cloudwatch_embedded::lambda::handler::run(metrics, |event| handler(event, policy_store)).await;
 
     match result {
         Ok(message) => {
@@ -40,12 +68,32 @@ async fn main() -> Result<(), LambdaRuntimeError> {
     }
 }
 
+/// Handles the event with the latest configuration
+// Ignore for code coverage
+#[cfg(not(tarpaulin_include))]
+async fn handler(event: LambdaEvent<JsonValue>, policy_store: &PolicyStore<Ssm>) -> Result<JsonValue, LambdaRuntimeError> {
+    match policy_store.config().await {
+        Ok(config) => func(event, &config).await,
+        Err(error) => {
+            error!("ERROR in Lambda function: {}", error);
+            Err(error.into())
+        }
+    }
+}
+
 // Ignore for code coverage
 #[cfg(not(tarpaulin_include))]
-async fn func(event: LambdaEvent<JsonValue>) -> Result<JsonValue, LambdaRuntimeError> {
+async fn func(event: LambdaEvent<JsonValue>, config: &Config) -> Result<JsonValue, LambdaRuntimeError> {
     debug!("Recevied payload: {}. Context: {:?}", event.payload, event.context);
-    let client = cloudwatch_logs().await;
-    let result = process_all_log_groups(client).await;
+    let sweep = Sweep::from_event(&event, config.dry_run);
+    let reporter = reporter(config).await;
+    let result = match config.accounts.is_empty() && config.regions.is_empty() {
+        true => process_all_log_groups(RateLimitedCloudWatchLogs::new(cloudwatch_logs().await), sweep, &config.policy, &reporter).await,
+        false => match pending_targets(&event.payload, &config.accounts, &config.regions) {
+            Ok(targets) => process_targets(targets, sweep, &config.policy, &reporter, |target| client_for_target(target, config)).await,
+            Err(error) => Err(error),
+        },
+    };
 
     match result {
         Ok(message) => Ok(message),
@@ -56,108 +104,130 @@ async fn func(event: LambdaEvent<JsonValue>) -> Result<JsonValue, LambdaRuntimeE
     }
 }
 
-async fn process_all_log_groups(
-    cloudwatch_logs_client: impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource,
-) -> Result<JsonValue, Error> {
-    let mut errors = vec![];
-    let mut total_groups = 0;
-    let mut updated = 0;
-    let mut already_has_retention = 0;
-    let mut already_tagged_with_retention = 0;
-
-    let mut next_token: Option<String> = None;
-    loop {
-        let result = cloudwatch_logs_client.describe_log_groups(None, next_token.take()).await?;
-
-        for log_group in result.log_groups() {
-            total_groups += 1;
-            match process_log_group(log_group, &cloudwatch_logs_client).await {
-                Ok(result) => match result {
-                    UpdateResult::AlreadyHasRetention => already_has_retention += 1,
-                    UpdateResult::AlreadyTaggedWithRetention => already_tagged_with_retention += 1,
-                    UpdateResult::Updated => updated += 1,
-                },
-                Err(e) => {
-                    error!("Failure updating retention: {}", e);
-                    errors.push(e);
-                }
-            }
+/// Targets still to sweep. A multi-target sweep which ran out of time returns the `pending` targets it did not finish;
+/// passing them back in the payload resumes just those. Otherwise every combination of `accounts` and `regions` is swept.
+fn pending_targets(payload: &JsonValue, accounts: &[String], regions: &[String]) -> Result<Vec<PendingTarget>, Error> {
+    match payload.get("pending") {
+        Some(pending) => serde_json::from_value(pending.clone()).map_err(|e| Error {
+            message: format!("Could not parse pending targets `{}`: {}", pending, e),
+            severity: Severity::Error,
+        }),
+        None => Ok(SweepTarget::all(accounts, regions)
+            .into_iter()
+            .map(|target| PendingTarget { target, next_token: None })
+            .collect()),
+    }
+}
+
+/// Sweeps each target in turn with its own client. A target which fails, e.g. because its role cannot be assumed,
+/// is reported in `failed` and the `FailedTargets` metric without stopping the others or failing the invocation,
+/// so that the `pending` targets are still returned. Failed targets are pending too, from the page they started at, so that resuming retries them.
+async fn process_targets<C, F, Fut>(
+    targets: Vec<PendingTarget>,
+    sweep: Sweep,
+    policy: &DecisionPolicy,
+    reporter: &Reporter<impl WriteReport>,
+    client_for_target: F,
+) -> Result<JsonValue, Error>
+where
+    C: DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource,
+    F: Fn(SweepTarget) -> Fut,
+    Fut: Future<Output = Result<C, Error>>,
+{
+    let mut results = vec![];
+    let mut pending = vec![];
+    let mut failed = vec![];
+
+    for PendingTarget { target, next_token } in targets {
+        // Targets not started before the deadline are resumed from where they were next time
+        if sweep.out_of_time() {
+            pending.push(PendingTarget { target, next_token });
+            continue;
         }
 
-        match result.next_token {
-            Some(token) => next_token = Some(token),
-            None => break,
+        info!("Sweeping {}", target);
+        let target_sweep = Sweep {
+            target: target.clone(),
+            next_token: next_token.clone(),
+            ..sweep.clone()
+        };
+        let failed_metric = target_sweep.with_dimensions(Metric::new(MetricName::FailedTargets, 1));
+        let result = match client_for_target(target.clone()).await {
+            Ok(client) => process_all_log_groups(client, target_sweep, policy, reporter).await,
+            Err(error) => Err(error),
+        };
+
+        let mut entry = json!(target);
+        match result {
+            Ok(result) => {
+                if result["complete"] == json!(false) {
+                    let next_token = result["nextToken"].as_str().map(str::to_string);
+                    pending.push(PendingTarget {
+                        target: target.clone(),
+                        next_token,
+                    });
+                }
+                entry["result"] = result;
+            }
+            Err(error) => {
+                error!("Failed to sweep {}: {}", target, error);
+                entry["error"] = json!(error.message);
+                metric_publisher::publish_metric(failed_metric);
+                failed.push(target.to_string());
+                pending.push(PendingTarget {
+                    target: target.clone(),
+                    next_token,
+                });
+            }
         }
+        results.push(entry);
     }
 
-    let metrics = vec![
-        Metric::new(MetricName::Total, total_groups),
-        Metric::new(MetricName::Updated, updated),
-        Metric::new(MetricName::AlreadyHasRetention, already_has_retention),
-        Metric::new(MetricName::AlreadyTaggedWithRetention, already_tagged_with_retention),
-        Metric::new(MetricName::Errored, errors.len() as u64),
-    ];
-    metric_publisher::publish_metrics(metrics);
-
-    match errors.is_empty() {
-        true => Ok(
-            json!({"message": "Success", "totalGroups": total_groups, "updated": updated, "alreadyHasRetention": already_has_retention, "alreadyTaggedWithRetention": already_tagged_with_retention}),
-        ),
+    let mut response = json!({"results": results, "complete": pending.is_empty(), "pending": pending, "failed": failed});
+    response["message"] = match failed.is_empty() {
+        true => json!("Success"),
         false => {
-            error!("Failed to update some log group retentions: {:?}", &errors);
-            Err(Error {
-                message: format!("Failed to update some log group retentions: {:?}", &errors),
-                severity: Severity::Error,
-            })
+            error!("Failed to sweep {:?}", failed);
+            json!(format!("Failed to sweep {:?}", failed))
         }
-    }
+    };
+    Ok(response)
 }
 
-async fn process_log_group(
-    log_group: &LogGroup,
-    client: &(impl PutRetentionPolicy + ListTagsForResource + TagResource),
-) -> Result<UpdateResult, LambdaRuntimeError> {
-    let log_group_arn = log_group.arn().expect("Log group ARN unexpectedly empty.").replace(":*", ""); // Some ARNs (all ARNs?) have :* on the end, but list-tags-for-resource cannot accept that part
-    let log_group_name = log_group.log_group_name().expect("Log group name unexpectedly empty.");
-    let log_group_retention = log_group.retention_in_days().unwrap_or(0);
-
-    debug!("Working on {}", log_group_arn);
-
-    if log_group_retention != 0 {
-        debug!(
-            "Log group {} has retention of {} days already. Not setting.",
-            log_group_name, log_group_retention
-        );
-        return Ok(UpdateResult::AlreadyHasRetention);
-    }
-
-    let tags = client.list_tags_for_resource(&log_group_arn).await?;
-    if let Some(retention) = tags.tags().and_then(|tags| tags.get("retention")) {
-        info!(
-            "Not setting retention for {} because tag `retention`=`{}` exists on it.",
-            log_group_name, retention
-        );
-        return Ok(UpdateResult::AlreadyTaggedWithRetention);
+/// Uploads every report to `report_bucket` if it is set. Otherwise large reports are written to the Lambda's logs.
+// Ignore for code coverage
+#[cfg(not(tarpaulin_include))]
+async fn reporter(config: &Config) -> Reporter<Box<dyn WriteReport + Send + Sync>> {
+    match &config.report_bucket {
+        Some(bucket) => {
+            let sink = S3ReportSink::new(s3().await, bucket.clone(), config.report_prefix.clone(), config.report_format);
+            Reporter::new(Box::new(sink) as Box<dyn WriteReport + Send + Sync>, config.report_inline_limit).always_write()
+        }
+        None => Reporter::new(Box::new(LogReportSink), config.report_inline_limit),
     }
+}
 
-    let new_retention = retention();
-    client.put_retention_policy(log_group_name, new_retention).await?;
-    info!("Set retention of {} days on {}.", new_retention, log_group_name);
-
-    if let Some(tags) = log_group_tags() {
-        client.tag_resource(&log_group_arn, tags).await?;
-        info!("Tagged {}.", log_group_arn);
-    }
+/// Assumes `cross_account_role_name` in the target account, if any, and builds a client for the target region
+// Ignore for code coverage
+#[cfg(not(tarpaulin_include))]
+async fn client_for_target(target: SweepTarget, config: &Config) -> Result<RateLimitedCloudWatchLogs<CloudWatchLogs>, Error> {
+    let credentials = match &target.account_id {
+        Some(account_id) => {
+            let role_name = config.cross_account_role_name.as_deref().expect("Validated by Config::from_source");
+            Some(assume_role(&sts().await, &role_arn(&config.aws_partition, account_id, role_name)).await?)
+        }
+        None => None,
+    };
 
-    Ok(UpdateResult::Updated)
+    Ok(RateLimitedCloudWatchLogs::new(cloudwatch_logs_for(target.region, credentials).await))
 }
-
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, time::SystemTime};
 
     use super::*;
-    use mockall::{mock, predicate};
+    use mockall::mock;
+    use terraform_aws_default_log_retention::config::ConfigSource;
 
     use async_trait::async_trait;
     use aws_sdk_cloudwatchlogs::{
@@ -165,312 +235,128 @@ mod tests {
             describe_log_groups::DescribeLogGroupsOutput, list_tags_for_resource::ListTagsForResourceOutput, put_retention_policy::PutRetentionPolicyOutput,
             tag_resource::TagResourceOutput,
         },
-        types::{
-            error::{DataAlreadyAcceptedException, InvalidOperationException, ResourceAlreadyExistsException},
-            LogGroup,
-        },
+        types::{error::InvalidOperationException, LogGroup},
         Error as CloudWatchLogsError,
     };
 
-    use terraform_aws_default_log_retention::cloudwatch_logs_traits::{PutRetentionPolicy, TagResource};
-
-    #[ctor::ctor]
-    fn init() {
-        std::env::set_var("log_group_tags", "{}");
+    fn policy() -> DecisionPolicy {
+        let source = ConfigSource {
+            log_group_tags: Some("{}".to_string()),
+            ..Default::default()
+        };
+        Config::from_source(&source).unwrap().policy
     }
 
-    #[tokio::test]
-    async fn test_process_all_log_group_success() {
-        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
-        mock_cloud_watch_logs_client
-            .expect_describe_log_groups()
-            .with(predicate::eq(None), predicate::eq(None))
-            .returning(|_, _| {
-                Ok(DescribeLogGroupsOutput::builder()
-                    .log_groups(
-                        LogGroup::builder()
-                            .log_group_name("MyLogGroupWasCreated")
-                            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
-                            .retention_in_days(0)
-                            .build(),
-                    )
-                    .log_groups(
-                        LogGroup::builder()
-                            .log_group_name("AnotherOneWithoutRetention")
-                            .arn("arn:aws:logs:123:us-west-2:log-group/AnotherOneWithoutRetention:*")
-                            .retention_in_days(0)
-                            .build(),
-                    )
-                    .next_token("NextOnesPlease")
-                    .build())
-            });
-        mock_cloud_watch_logs_client
-            .expect_describe_log_groups()
-            .with(predicate::eq(None), predicate::eq(Some("NextOnesPlease".to_string())))
-            .returning(|_, _| {
-                Ok(DescribeLogGroupsOutput::builder()
-                    .log_groups(
-                        LogGroup::builder()
-                            .log_group_name("SecondLogGroupAlreadyHasRetention")
-                            .arn("arn:aws:logs:123:us-west-2:log-group/SecondLogGroupAlreadyHasRetention:*")
-                            .retention_in_days(90)
-                            .build(),
-                    )
-                    .build())
-            });
-
-        mock_cloud_watch_logs_client
-            .expect_list_tags_for_resource()
-            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_put_retention_policy()
-            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(30))
-            .once()
-            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_put_retention_policy()
-            .with(predicate::eq("AnotherOneWithoutRetention"), predicate::eq(30))
-            .once()
-            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_tag_resource()
-            .with(
-                predicate::eq("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated"),
-                predicate::eq(HashMap::new()),
-            )
-            .once()
-            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_tag_resource()
-            .with(
-                predicate::eq("arn:aws:logs:123:us-west-2:log-group/AnotherOneWithoutRetention"),
-                predicate::eq(HashMap::new()),
-            )
-            .once()
-            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
-
-        let result = process_all_log_groups(mock_cloud_watch_logs_client).await.expect("Should not fail");
-
-        insta::assert_snapshot!(result);
+    fn target(account_id: Option<&str>, region: Option<&str>) -> SweepTarget {
+        SweepTarget {
+            account_id: account_id.map(str::to_string),
+            region: region.map(str::to_string),
+        }
     }
 
-    #[tokio::test]
-    async fn test_process_all_log_group_single_already_tagged_with_retention() {
-        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
-        mock_cloud_watch_logs_client.expect_describe_log_groups().returning(|_, _| {
-            Ok(DescribeLogGroupsOutput::builder()
-                .log_groups(
-                    LogGroup::builder()
-                        .log_group_name("MyLogGroupWasCreated")
-                        .arn("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails:*")
-                        .retention_in_days(0)
-                        .build(),
-                )
-                .build())
-        });
-
-        mock_cloud_watch_logs_client
-            .expect_list_tags_for_resource()
-            .returning(|_| Ok(ListTagsForResourceOutput::builder().tags("retention", "DoNotTouch").build()));
-
-        let result = process_all_log_groups(mock_cloud_watch_logs_client).await.expect("Should not fail");
-
-        insta::assert_snapshot!(result);
+    fn pending(account_id: Option<&str>, region: Option<&str>, next_token: Option<&str>) -> PendingTarget {
+        PendingTarget {
+            target: target(account_id, region),
+            next_token: next_token.map(str::to_string),
+        }
     }
 
     #[tokio::test]
-    async fn test_process_all_log_group_partial_success() {
-        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
-        mock_cloud_watch_logs_client
-            .expect_describe_log_groups()
-            .with(predicate::eq(None), predicate::eq(None))
-            .returning(|_, _| {
-                Ok(DescribeLogGroupsOutput::builder()
-                    .log_groups(
-                        LogGroup::builder()
-                            .log_group_name("MyLogGroupWasCreated")
-                            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
-                            .retention_in_days(0)
-                            .build(),
-                    )
-                    .log_groups(
-                        LogGroup::builder()
-                            .log_group_name("AnotherOneWithoutRetention")
-                            .arn("arn:aws:logs:123:us-west-2:log-group/AnotherOneWithoutRetention:*")
-                            .retention_in_days(0)
-                            .build(),
-                    )
-                    .log_groups(
-                        LogGroup::builder()
-                            .log_group_name("NoRetentionAndGetTagsCallFails")
-                            .arn("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails:*")
-                            .retention_in_days(0)
-                            .build(),
-                    )
-                    .next_token("MoreToCome")
-                    .build())
-            });
-        mock_cloud_watch_logs_client
-            .expect_describe_log_groups()
-            .with(predicate::eq(None), predicate::eq(Some("MoreToCome".to_string())))
-            .returning(|_, _| {
-                Ok(DescribeLogGroupsOutput::builder()
-                    .log_groups(
-                        LogGroup::builder()
-                            .log_group_name("SecondLogGroupAlreadyHasRetention")
-                            .arn("arn:aws:logs:123:us-west-2:log-group/SecondLogGroupAlreadyHasRetention:*")
-                            .retention_in_days(90)
-                            .build(),
-                    )
-                    .build())
-            });
-
-        mock_cloud_watch_logs_client
-            .expect_list_tags_for_resource()
-            .with(predicate::eq("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails"))
-            .returning(|_| {
-                Err(CloudWatchLogsError::DataAlreadyAcceptedException(
-                    DataAlreadyAcceptedException::builder().build(),
-                ))
-            });
-
-        mock_cloud_watch_logs_client
-            .expect_list_tags_for_resource()
-            .with(predicate::ne("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails"))
-            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_put_retention_policy()
-            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(30))
-            .once()
-            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_put_retention_policy()
-            .with(predicate::eq("AnotherOneWithoutRetention"), predicate::eq(30))
-            .once()
-            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_tag_resource()
-            .with(
-                predicate::eq("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated"),
-                predicate::eq(HashMap::new()),
-            )
-            .once()
-            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_tag_resource()
-            .with(
-                predicate::eq("arn:aws:logs:123:us-west-2:log-group/AnotherOneWithoutRetention"),
-                predicate::eq(HashMap::new()),
-            )
-            .once()
-            .returning(|_, _| Err(CloudWatchLogsError::InvalidOperationException(InvalidOperationException::builder().build())));
-
-        let result = process_all_log_groups(mock_cloud_watch_logs_client).await.expect_err("Should fail");
+    async fn test_process_targets_isolates_failures() {
+        let client_for_target = |target: SweepTarget| async move {
+            let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+            match (target.account_id.as_deref(), target.region.as_deref()) {
+                (Some("111111111111"), Some("us-east-1")) => {
+                    mock_cloud_watch_logs_client.expect_describe_log_groups().returning(|_, _| {
+                        Ok(DescribeLogGroupsOutput::builder()
+                            .log_groups(
+                                LogGroup::builder()
+                                    .log_group_name("AlreadyHasRetention")
+                                    .arn("arn:aws:logs:us-east-1:111111111111:log-group:AlreadyHasRetention:*")
+                                    .retention_in_days(90)
+                                    .build(),
+                            )
+                            .build())
+                    });
+                    mock_cloud_watch_logs_client
+                        .expect_list_tags_for_resource()
+                        .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+                }
+                (Some("111111111111"), _) => {
+                    mock_cloud_watch_logs_client
+                        .expect_describe_log_groups()
+                        .returning(|_, _| Err(CloudWatchLogsError::InvalidOperationException(InvalidOperationException::builder().build())));
+                }
+                _ => {
+                    return Err(Error {
+                        message: "Not authorized to perform sts:AssumeRole".to_string(),
+                        severity: Severity::Error,
+                    })
+                }
+            }
+            Ok(mock_cloud_watch_logs_client)
+        };
+
+        let targets = vec![
+            pending(Some("111111111111"), Some("us-east-1"), None),
+            pending(Some("111111111111"), Some("eu-west-1"), Some("Page2")),
+            pending(Some("222222222222"), Some("us-east-1"), None),
+        ];
+        let result = process_targets(
+            targets,
+            Sweep::default(),
+            &policy(),
+            &Reporter::new(LogReportSink, usize::MAX),
+            client_for_target,
+        )
+        .await
+        .expect("Should not fail");
 
         insta::assert_snapshot!(result);
     }
 
     #[tokio::test]
-    async fn test_process_log_group_success() {
-        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
-        let log_group_arn = "arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated";
-
-        mock_cloud_watch_logs_client
-            .expect_list_tags_for_resource()
-            .with(predicate::eq(log_group_arn))
-            .once()
-            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_put_retention_policy()
-            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(30))
-            .once()
-            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
-
-        mock_cloud_watch_logs_client
-            .expect_tag_resource()
-            .with(predicate::eq(log_group_arn), predicate::eq(HashMap::new()))
-            .once()
-            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
-
-        let log_group = LogGroup::builder()
-            .log_group_name("MyLogGroupWasCreated")
-            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
-            .retention_in_days(0)
-            .build();
-
-        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client).await.expect("Should not fail");
-
-        assert_eq!(UpdateResult::Updated, result);
-    }
-
-    #[tokio::test]
-    async fn test_process_log_group_retention_already_set() {
-        let mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
-
-        let log_group = LogGroup::builder()
-            .log_group_name("MyLogGroupWasCreated")
-            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
-            .retention_in_days(30)
-            .build();
-
-        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client).await.expect("Should not fail");
-
-        assert_eq!(UpdateResult::AlreadyHasRetention, result);
+    async fn test_process_targets_out_of_time() {
+        let client_for_target = |_: SweepTarget| async {
+            let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+            mock_cloud_watch_logs_client.expect_describe_log_groups().never();
+            Ok(mock_cloud_watch_logs_client)
+        };
+
+        let targets = vec![pending(None, Some("us-east-1"), Some("Page3")), pending(None, Some("eu-west-1"), None)];
+        let sweep = Sweep {
+            deadline: Some(SystemTime::now()),
+            ..Default::default()
+        };
+        let result = process_targets(targets, sweep, &policy(), &Reporter::new(LogReportSink, usize::MAX), client_for_target)
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(json!(false), result["complete"]);
+        assert_eq!(
+            json!([{"region": "us-east-1", "nextToken": "Page3"}, {"region": "eu-west-1", "nextToken": null}]),
+            result["pending"]
+        );
     }
 
-    #[tokio::test]
-    async fn test_process_log_group_no_retention_but_tag_present() {
-        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
-
-        mock_cloud_watch_logs_client.expect_list_tags_for_resource().once().returning(|_| {
-            Ok(ListTagsForResourceOutput::builder()
-                .tags("retention", "I know what I'm doing and I've tagged this group. Leave me alone!")
-                .build())
-        });
-
-        let log_group = LogGroup::builder()
-            .log_group_name("MyLogGroupWasCreated")
-            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
-            .retention_in_days(0)
-            .build();
-
-        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client).await.expect("Should not fail");
-
-        assert_eq!(UpdateResult::AlreadyTaggedWithRetention, result);
-    }
+    #[test]
+    fn test_pending_targets() {
+        let accounts = vec!["111111111111".to_string()];
 
-    #[tokio::test]
-    async fn test_process_log_group_fails() {
-        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
-        mock_cloud_watch_logs_client
-            .expect_list_tags_for_resource()
-            .with(predicate::eq("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails"))
-            .once()
-            .returning(|_| {
-                // This type of error would never happen. Luckily it doesn't matter -- we only care that an error happened.
-                Err(CloudWatchLogsError::ResourceAlreadyExistsException(
-                    ResourceAlreadyExistsException::builder().build(),
-                ))
-            });
-
-        let log_group = LogGroup::builder()
-            .log_group_name("MyLogGroupWasCreated")
-            .arn("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails:*")
-            .retention_in_days(0)
-            .build();
-
-        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client).await.expect_err("Should fail");
-
-        insta::assert_debug_snapshot!(result);
+        assert_eq!(
+            vec![pending(Some("111111111111"), None, None)],
+            pending_targets(&json!({}), &accounts, &[]).unwrap()
+        );
+        assert_eq!(
+            vec![pending(Some("111111111111"), Some("us-west-2"), Some("abc"))],
+            pending_targets(
+                &json!({"pending": [{"accountId": "111111111111", "region": "us-west-2", "nextToken": "abc"}]}),
+                &accounts,
+                &[]
+            )
+            .unwrap()
+        );
+        pending_targets(&json!({"pending": "nope"}), &accounts, &[]).unwrap_err();
     }
 
     // Required to mock multiple traits at a time
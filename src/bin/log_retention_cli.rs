@@ -0,0 +1,400 @@
+use std::{process::ExitCode, sync::Arc, time::SystemTime};
+
+use aws_smithy_types::date_time::{DateTime, Format};
+use clap::{Parser, ValueEnum};
+use log::error;
+use metrics::{Counter, CounterFn, Gauge, Histogram, Key, KeyName, Recorder, SharedString, Unit};
+use serde_json::{json, Value as JsonValue};
+use terraform_aws_default_log_retention::{
+    config::{self, Config, ConfigSource},
+    error::{Error, Severity},
+    global::cloudwatch_logs_for_profile,
+    rate_limiter::RateLimitedCloudWatchLogs,
+    report::{LogGroupReport, LogReportSink, ReportFormat, Reporter},
+    retention_days::RetentionDays,
+    rollback::{rollback_from_report, rollback_from_tags, Rollback},
+    sweep::{process_all_log_groups, Sweep},
+};
+
+/// Sets retention on every log group in a region, like the global retention setter Lambda, from a laptop or CI job.
+/// Anything not given as a flag is read from the same configuration as the Lambdas, e.g. `log_retention_rules`.
+/// With `--rollback`, undoes the changes they made instead.
+#[derive(Debug, Parser)]
+#[command(name = "log_retention_cli", version)]
+struct Args {
+    /// Region to sweep. Defaults to the profile's or environment's region.
+    #[arg(long)]
+    region: Option<String>,
+
+    /// Named AWS profile to use. Defaults to the environment's credentials.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// JSON, YAML or TOML file to read the configuration from, like the Lambdas' `config_file`. Defaults to the environment variables.
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Retention in days for log groups no rule matches. Defaults to `log_retention_in_days`, or 30.
+    #[arg(long)]
+    retention: Option<i32>,
+
+    /// Tag to put on log groups given retention, as KEY=VALUE. May be repeated. Defaults to `log_group_tags`.
+    #[arg(long = "tag", value_parser = parse_tag)]
+    tags: Vec<(String, String)>,
+
+    /// Only sweep log groups whose names start with this
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Print what would change without changing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Restore the retention recorded in the `previous-retention` tag of each log group this module changed, instead of setting retention
+    #[arg(long)]
+    rollback: bool,
+
+    /// Roll back the log groups a report says were changed, rather than those with a recorded change. JSON lines, or CSV if the path ends `.csv`.
+    #[arg(long, requires = "rollback")]
+    from_report: Option<String>,
+
+    /// Only roll back changes made at or after this time, e.g. `2024-01-31T02:00:00Z`
+    #[arg(long, requires = "rollback", conflicts_with = "from_report", value_parser = parse_time)]
+    changed_since: Option<SystemTime>,
+
+    /// Format of the metrics and summary printed to stdout. `json` prints one JSON object per line.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[tokio::main]
+// Ignore for code coverage
+#[cfg(not(tarpaulin_include))]
+async fn main() -> ExitCode {
+    // Logs go to stderr, so stdout only has metrics and the summary
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn,terraform_aws_default_log_retention=info"))
+        .format_timestamp(None)
+        .init();
+
+    let args = Args::parse();
+    let output = args.output;
+    let rollback = args.rollback;
+    metrics::set_boxed_recorder(Box::new(StdoutRecorder { output })).expect("Could not install metric recorder.");
+
+    match run(args).await {
+        Ok(response) => {
+            match output {
+                OutputFormat::Text if rollback => println!("{}", render_rollback_text(&response)),
+                OutputFormat::Text => println!("{}", render_text(&response)),
+                OutputFormat::Json => println!("{}", response),
+            }
+            // Log groups which failed are counted rather than failing the sweep; they still fail the command
+            match response["errored"].as_u64().unwrap_or(0) {
+                0 => ExitCode::SUCCESS,
+                _ => ExitCode::FAILURE,
+            }
+        }
+        Err(error) => {
+            error!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Ignore for code coverage
+#[cfg(not(tarpaulin_include))]
+async fn run(args: Args) -> Result<JsonValue, Error> {
+    let config = config(&args)?;
+    let dry_run = args.dry_run || config.dry_run;
+    let client = RateLimitedCloudWatchLogs::new(cloudwatch_logs_for_profile(args.profile, args.region).await);
+
+    if args.rollback {
+        let rollback = Rollback {
+            dry_run,
+            log_group_name_prefix: args.prefix,
+            changed_since: args.changed_since,
+            concurrency: config.policy.concurrency,
+            started_at: SystemTime::now(),
+        };
+        return match args.from_report {
+            Some(path) => rollback_from_report(client, &rollback, &config.policy.tagging, &read_report(&path)?).await,
+            None => rollback_from_tags(client, &rollback, &config.policy.tagging).await,
+        };
+    }
+
+    let sweep = Sweep {
+        dry_run,
+        log_group_name_prefix: args.prefix,
+        ..Default::default()
+    };
+
+    // The whole report is always printed
+    let reporter = Reporter::new(LogReportSink, usize::MAX);
+
+    process_all_log_groups(client, sweep, &config.policy, &reporter).await
+}
+
+/// The Lambdas' configuration, with the fallback retention and tags replaced by any given as flags
+fn config(args: &Args) -> Result<Config, Error> {
+    let source = match &args.config {
+        Some(path) => ConfigSource::from_file(path)?,
+        None => ConfigSource::from_env()?,
+    };
+    let mut config = Config::from_source(&source).map_err(config::combine)?;
+
+    if let Some(retention_in_days) = args.retention {
+        let retention_in_days = RetentionDays::new(retention_in_days, config.policy.invalid_retention_strategy)?;
+        config.policy.retention_rules = config.policy.retention_rules.with_default_retention_in_days(retention_in_days);
+    }
+    if !args.tags.is_empty() {
+        config.policy.retention_rules = config.policy.retention_rules.with_default_tags(Some(args.tags.iter().cloned().collect()));
+    }
+
+    Ok(config)
+}
+
+fn parse_tag(tag: &str) -> Result<(String, String), Error> {
+    match tag.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(Error {
+            message: format!("Tag `{}` must be written as KEY=VALUE", tag),
+            severity: Severity::Error,
+        }),
+    }
+}
+
+fn parse_time(time: &str) -> Result<SystemTime, Error> {
+    DateTime::from_str(time, Format::DateTime)
+        .ok()
+        .and_then(|time| SystemTime::try_from(time).ok())
+        .ok_or_else(|| Error {
+            message: format!("Time `{}` must be written like 2024-01-31T02:00:00Z", time),
+            severity: Severity::Error,
+        })
+}
+
+fn read_report(path: &str) -> Result<Vec<LogGroupReport>, Error> {
+    let contents = std::fs::read_to_string(path).map_err(|e| Error {
+        message: format!("Could not read report {}: {}", path, e),
+        severity: Severity::Error,
+    })?;
+    ReportFormat::from_path(path).parse(&contents)
+}
+
+fn render_text(response: &JsonValue) -> String {
+    let mut lines = vec![];
+    if response["dryRun"] == json!(true) {
+        lines.push("Dry run. No changes were made.".to_string());
+    }
+
+    lines.push(format!("Log groups: {}", response["totalGroups"]));
+    lines.push(format!("Updated: {}", response["updated"]));
+    if let Some(updated_by_rule) = response["updatedByRule"].as_object() {
+        for (rule, count) in updated_by_rule {
+            lines.push(format!("  {}: {}", rule, count));
+        }
+    }
+    lines.push(format!("Already had retention: {}", response["alreadyHasRetention"]));
+    lines.push(format!("Tagged with retention: {}", response["alreadyTaggedWithRetention"]));
+    lines.push(format!("Reduced to maximum: {}", response["reducedToMaximum"]));
+    lines.push(format!("Raised to minimum: {}", response["raisedToMinimum"]));
+    lines.push(format!("Excluded: {}", response["excluded"]));
+    lines.push(format!("Errored: {}", response["errored"]));
+
+    lines.join("\n")
+}
+
+fn render_rollback_text(response: &JsonValue) -> String {
+    let mut lines = vec![];
+    if response["dryRun"] == json!(true) {
+        lines.push("Dry run. No changes were made.".to_string());
+    }
+
+    lines.push(format!("Log groups: {}", response["totalGroups"]));
+    lines.push(format!("Restored: {}", response["restored"]));
+    lines.push(format!("Already restored: {}", response["alreadyRestored"]));
+    lines.push(format!("Marked without a record of the previous retention: {}", response["noChangeRecord"]));
+    lines.push(format!("Errored: {}", response["errored"]));
+
+    lines.join("\n")
+}
+
+/// Prints metrics published by `metric_publisher` to stdout, in place of the embedded metric format collector the Lambdas use
+struct StdoutRecorder {
+    output: OutputFormat,
+}
+
+impl Recorder for StdoutRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key) -> Counter {
+        Counter::from_arc(Arc::new(StdoutCounter {
+            key: key.clone(),
+            output: self.output,
+        }))
+    }
+
+    // Only counters are published
+    fn register_gauge(&self, _key: &Key) -> Gauge {
+        Gauge::noop()
+    }
+
+    fn register_histogram(&self, _key: &Key) -> Histogram {
+        Histogram::noop()
+    }
+}
+
+struct StdoutCounter {
+    key: Key,
+    output: OutputFormat,
+}
+
+impl CounterFn for StdoutCounter {
+    fn increment(&self, value: u64) {
+        println!("{}", format_metric(&self.key, value, self.output));
+    }
+
+    fn absolute(&self, value: u64) {
+        println!("{}", format_metric(&self.key, value, self.output));
+    }
+}
+
+fn format_metric(key: &Key, value: u64, output: OutputFormat) -> String {
+    match output {
+        OutputFormat::Text => {
+            let dimensions: Vec<String> = key.labels().map(|label| format!("{}={}", label.key(), label.value())).collect();
+            match dimensions.is_empty() {
+                true => format!("metric {} {}", key.name(), value),
+                false => format!("metric {} {} ({})", key.name(), value, dimensions.join(", ")),
+            }
+        }
+        OutputFormat::Json => {
+            let dimensions: serde_json::Map<String, JsonValue> = key.labels().map(|label| (label.key().to_string(), json!(label.value()))).collect();
+            json!({"metric": key.name(), "value": value, "dimensions": dimensions}).to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use metrics::Label;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let args = Args::try_parse_from([
+            "log_retention_cli",
+            "--region",
+            "us-west-2",
+            "--profile",
+            "sandbox",
+            "--retention",
+            "14",
+            "--tag",
+            "team=platform",
+            "--tag",
+            "cost-center=",
+            "--prefix",
+            "/aws/lambda/",
+            "--dry-run",
+            "--output",
+            "json",
+        ])
+        .unwrap();
+
+        assert_eq!(Some("us-west-2".to_string()), args.region);
+        assert_eq!(Some("sandbox".to_string()), args.profile);
+        assert_eq!(Some(14), args.retention);
+        assert_eq!(
+            vec![("team".to_string(), "platform".to_string()), ("cost-center".to_string(), "".to_string())],
+            args.tags
+        );
+        assert_eq!(Some("/aws/lambda/".to_string()), args.prefix);
+        assert!(args.dry_run);
+        assert_eq!(OutputFormat::Json, args.output);
+    }
+
+    #[test]
+    fn test_parse_rollback_args() {
+        let args = Args::try_parse_from(["log_retention_cli", "--rollback", "--changed-since", "2023-11-14T22:13:20Z"]).unwrap();
+
+        assert!(args.rollback);
+        assert_eq!(Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)), args.changed_since);
+
+        Args::try_parse_from(["log_retention_cli", "--from-report", "report.jsonl"]).unwrap_err();
+        Args::try_parse_from(["log_retention_cli", "--rollback", "--changed-since", "yesterday"]).unwrap_err();
+        Args::try_parse_from([
+            "log_retention_cli",
+            "--rollback",
+            "--from-report",
+            "report.jsonl",
+            "--changed-since",
+            "2023-11-14T22:13:20Z",
+        ])
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_args_invalid_tag() {
+        Args::try_parse_from(["log_retention_cli", "--tag", "team"]).unwrap_err();
+        Args::try_parse_from(["log_retention_cli", "--tag", "=platform"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_config_from_flags() {
+        let args = Args::try_parse_from(["log_retention_cli", "--retention", "14", "--tag", "team=platform"]).unwrap();
+
+        let rule_match = config(&args).unwrap().policy.retention_rules.evaluate("/aws/lambda/abc");
+
+        assert_eq!(14, rule_match.retention_in_days.days());
+        assert_eq!(Some(HashMap::from([("team".to_string(), "platform".to_string())])), rule_match.tags);
+    }
+
+    #[test]
+    fn test_config_rejects_invalid_retention() {
+        let args = Args::try_parse_from(["log_retention_cli", "--retention", "45"]).unwrap();
+
+        let error = config(&args).unwrap_err();
+
+        assert_eq!(Severity::Error, error.severity);
+    }
+
+    #[test]
+    fn test_render_text() {
+        let response = json!({"message": "Success", "totalGroups": 5, "updated": 2, "alreadyHasRetention": 1, "alreadyTaggedWithRetention": 1, "reducedToMaximum": 1, "raisedToMinimum": 0, "excluded": 1, "errored": 0, "updatedByRule": {"lambda": 1, "default": 1}, "complete": true, "nextToken": null, "dryRun": true, "plan": []});
+
+        insta::assert_snapshot!(render_text(&response));
+    }
+
+    #[test]
+    fn test_render_rollback_text() {
+        let response = json!({"message": "Success", "totalGroups": 5, "restored": 2, "alreadyRestored": 1, "noChangeRecord": 1, "errored": 0, "dryRun": true, "report": []});
+
+        insta::assert_snapshot!(render_rollback_text(&response));
+    }
+
+    #[test]
+    fn test_format_metric() {
+        let key = Key::from_parts("Updated", vec![Label::new("Mode", "DryRun")]);
+
+        assert_eq!("metric Updated 3 (Mode=DryRun)", format_metric(&key, 3, OutputFormat::Text));
+        assert_eq!("metric Total 7", format_metric(&Key::from_name("Total"), 7, OutputFormat::Text));
+        assert_eq!(
+            json!({"metric": "Updated", "value": 3, "dimensions": {"Mode": "DryRun"}}).to_string(),
+            format_metric(&key, 3, OutputFormat::Json)
+        );
+    }
+}
@@ -17,8 +17,8 @@ Adding a new operation:
 
 use async_trait::async_trait;
 use aws_sdk_cloudwatchlogs::operation::{
-    describe_log_groups::DescribeLogGroupsOutput, list_tags_for_resource::ListTagsForResourceOutput, put_retention_policy::PutRetentionPolicyOutput,
-    tag_resource::TagResourceOutput,
+    delete_retention_policy::DeleteRetentionPolicyOutput, describe_log_groups::DescribeLogGroupsOutput, list_tags_for_resource::ListTagsForResourceOutput,
+    put_retention_policy::PutRetentionPolicyOutput, tag_resource::TagResourceOutput,
 };
 use aws_sdk_cloudwatchlogs::{Client as CloudWatchLogsClient, Error as CloudWatchLogsError};
 use std::collections::HashMap;
@@ -63,9 +63,15 @@ pub trait PutRetentionPolicy {
     async fn put_retention_policy(&self, log_group_name: &str, retention_in_days: i32) -> Result<PutRetentionPolicyOutput, CloudWatchLogsError>;
 }
 
+#[async_trait]
+pub trait DeleteRetentionPolicy {
+    // Makes a log group never expire again; used to roll back groups which had no retention
+    async fn delete_retention_policy(&self, log_group_name: &str) -> Result<DeleteRetentionPolicyOutput, CloudWatchLogsError>;
+}
+
 #[async_trait]
 pub trait TagResource {
-    // Add retention tag to a log group
+    // Add exactly these tags to a log group; see `TaggingPolicy` for what they are
     async fn tag_resource(&self, log_group_arn: &str, tags: HashMap<String, String>) -> Result<TagResourceOutput, CloudWatchLogsError>;
 }
 
@@ -110,17 +116,17 @@ impl PutRetentionPolicy for CloudWatchLogs {
     }
 }
 
+#[async_trait]
+impl DeleteRetentionPolicy for CloudWatchLogs {
+    async fn delete_retention_policy(&self, log_group_name: &str) -> Result<DeleteRetentionPolicyOutput, CloudWatchLogsError> {
+        Ok(self.client.delete_retention_policy().log_group_name(log_group_name).send().await?)
+    }
+}
+
 #[async_trait]
 impl TagResource for CloudWatchLogs {
     async fn tag_resource(&self, log_group_arn: &str, tags: HashMap<String, String>) -> Result<TagResourceOutput, CloudWatchLogsError> {
-        Ok(self
-            .client
-            .tag_resource()
-            .resource_arn(log_group_arn)
-            .set_tags(Some(tags))
-            .tags("retention", "Set by AWS Default Log Retention project.")
-            .send()
-            .await?)
+        Ok(self.client.tag_resource().resource_arn(log_group_arn).set_tags(Some(tags)).send().await?)
     }
 }
 
@@ -0,0 +1,584 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::{
+    error::{Error, Severity},
+    exclusions::Exclusions,
+    report::ReportFormat,
+    retention_days::{InvalidRetentionStrategy, RetentionDays},
+    retention_decision::DecisionPolicy,
+    retention_limits::{MaximumRetention, MinimumRetention, RetentionLimits},
+    retention_rules::{RetentionRule, RetentionRules},
+    retention_tag::{RetentionTagKeys, MARKER_TAG_VALUE, PREVIOUS_RETENTION_TAG_KEY, RETENTION_CHANGED_AT_TAG_KEY, RETENTION_TAG_KEY, ROLLED_BACK_AT_TAG_KEY},
+    tagging::TaggingPolicy,
+};
+
+/// Every setting of the Lambdas and the CLI, loaded and validated once at cold start and passed to whatever needs it
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub policy: DecisionPolicy,
+    /// Regions for the global retention setter to sweep. Empty means only the Lambda's own region.
+    pub regions: Vec<String>,
+    /// Accounts for the global retention setter to sweep by assuming `cross_account_role_name` in each. Empty means only the Lambda's own account.
+    pub accounts: Vec<String>,
+    pub cross_account_role_name: Option<String>,
+    /// Largest report returned in the global retention setter's response. Larger reports are written to the report sink instead.
+    pub report_inline_limit: usize,
+    /// Bucket to upload every global sweep's report to. Reports are not uploaded if it is None.
+    pub report_bucket: Option<String>,
+    /// Prefix of report keys in `report_bucket`, e.g. `log-retention/`
+    pub report_prefix: String,
+    pub report_format: ReportFormat,
+    pub metric_namespace: String,
+    /// SSM parameter holding settings layered on top of these, re-read by `PolicyStore` every `policy_parameter_ttl`
+    pub policy_parameter_name: Option<String>,
+    pub policy_parameter_ttl: Duration,
+    /// See `dry_run::is_dry_run`, which also honors a per-invocation flag
+    pub dry_run: bool,
+    pub aws_partition: String,
+}
+
+/// Settings as they are written, named after the environment variables which set them, e.g. `log_retention_in_days`.
+/// Unknown settings are ignored, and empty ones count as unset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigSource {
+    pub log_retention_in_days: Option<String>,
+    pub log_retention_rules: Option<String>,
+    pub log_group_tags: Option<String>,
+    pub invalid_retention_strategy: Option<String>,
+    pub max_retention_in_days: Option<String>,
+    pub max_retention_exemptions: Option<String>,
+    pub min_retention_in_days: Option<String>,
+    pub min_retention_prefixes: Option<String>,
+    pub retention_tag_key: Option<String>,
+    pub additional_opt_out_tag_keys: Option<String>,
+    pub marker_tag_key: Option<String>,
+    pub marker_tag_value: Option<String>,
+    pub marker_tag_enabled: Option<String>,
+    pub previous_retention_tag_key: Option<String>,
+    pub retention_changed_at_tag_key: Option<String>,
+    pub rolled_back_tag_key: Option<String>,
+    pub log_group_exclusions: Option<String>,
+    pub log_group_exclusions_file: Option<String>,
+    pub regions: Option<String>,
+    pub accounts: Option<String>,
+    pub cross_account_role_name: Option<String>,
+    pub concurrency: Option<String>,
+    pub report_inline_limit: Option<String>,
+    pub report_bucket: Option<String>,
+    pub report_prefix: Option<String>,
+    pub report_format: Option<String>,
+    pub metric_namespace: Option<String>,
+    pub policy_parameter_name: Option<String>,
+    pub policy_parameter_ttl_seconds: Option<String>,
+    pub dry_run: Option<String>,
+    pub aws_partition: Option<String>,
+}
+
+impl ConfigSource {
+    /// `config_file` if it is set, or else the environment variables
+    pub fn load() -> Result<Self, Error> {
+        match std::env::var("config_file") {
+            Ok(path) if !path.is_empty() => Self::from_file(&path),
+            _ => Self::from_env(),
+        }
+    }
+
+    /// The Lambda's environment variables, as set by Terraform
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_json(JsonValue::Object(
+            std::env::vars().map(|(name, value)| (name, JsonValue::String(value))).collect(),
+        ))
+    }
+
+    /// A JSON, YAML or TOML file, chosen by its extension. Lists and maps may be written as they are rather than as JSON strings.
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let extension = Path::new(path).extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        if !["json", "yaml", "yml", "toml"].contains(&extension) {
+            return Err(invalid_config(format!("config_file `{}` must end in .json, .yaml, .yml or .toml", path)));
+        }
+
+        let contents = std::fs::read_to_string(path).map_err(|e| invalid_config(format!("Could not read config_file `{}`: {}", path, e)))?;
+        let parse_error = |e: &dyn std::fmt::Display| invalid_config(format!("Could not parse config_file `{}`: {}", path, e));
+        let settings: JsonValue = match extension {
+            "json" => serde_json::from_str(&contents).map_err(|e| parse_error(&e))?,
+            "toml" => toml::from_str(&contents).map_err(|e| parse_error(&e))?,
+            _ => serde_yaml::from_str(&contents).map_err(|e| parse_error(&e))?,
+        };
+        Self::from_json(settings)
+    }
+
+    /// These settings, with those set in `other` taking their place
+    pub fn overlaid_with(&self, other: &ConfigSource) -> Self {
+        let mut settings = serde_json::to_value(self).expect("Settings are always valid JSON");
+        let overrides = serde_json::to_value(other).expect("Settings are always valid JSON");
+        if let (JsonValue::Object(settings), JsonValue::Object(overrides)) = (&mut settings, overrides) {
+            settings.extend(overrides.into_iter().filter(|(_, value)| !value.is_null()));
+        }
+        serde_json::from_value(settings).expect("Settings are always valid JSON")
+    }
+
+    /// Settings written as a JSON object, e.g. the value of the policy parameter
+    pub fn from_json_str(settings: &str) -> Result<Self, Error> {
+        let settings = serde_json::from_str(settings).map_err(|e| invalid_config(format!("Could not parse settings `{}`: {}", settings, e)))?;
+        Self::from_json(settings)
+    }
+
+    /// Settings from a JSON object. Values which are not strings, e.g. a list of rules, are kept as JSON text.
+    pub fn from_json(settings: JsonValue) -> Result<Self, Error> {
+        let settings = match settings {
+            JsonValue::Object(settings) => settings,
+            other => return Err(invalid_config(format!("Configuration must be a map of settings, not `{}`", other))),
+        };
+
+        let settings: JsonMap<String, JsonValue> = settings
+            .into_iter()
+            .filter_map(|(name, value)| match value {
+                JsonValue::Null => None,
+                JsonValue::String(value) if value.is_empty() => None,
+                JsonValue::String(value) => Some((name, JsonValue::String(value))),
+                other => Some((name, JsonValue::String(other.to_string()))),
+            })
+            .collect();
+        serde_json::from_value(JsonValue::Object(settings)).map_err(|e| invalid_config(format!("Could not read configuration: {}", e)))
+    }
+}
+
+impl Config {
+    /// Validates every setting, returning all the problems found rather than only the first
+    pub fn from_source(source: &ConfigSource) -> Result<Self, Vec<Error>> {
+        let mut errors = vec![];
+
+        let strategy = record(parse_or_default(&source.invalid_retention_strategy), &mut errors);
+        let default_retention = record(retention("log_retention_in_days", &source.log_retention_in_days, strategy), &mut errors)
+            .unwrap_or_else(|| RetentionDays::try_from(30).expect("30 is a valid retention"));
+        let rules = record(configured_rules(source, strategy), &mut errors);
+        let log_group_tags = record(log_group_tags(source), &mut errors);
+        let retention_limits = record(retention_limits(source, strategy), &mut errors);
+        let retention_tag_keys = record(retention_tag_keys(source), &mut errors);
+        let exclusions = record(exclusions(source), &mut errors);
+        let concurrency = record(positive("concurrency", &source.concurrency, 10), &mut errors);
+        let regions = record(string_list("regions", &source.regions), &mut errors);
+        let accounts = record(string_list("accounts", &source.accounts), &mut errors);
+        let report_inline_limit = record(report_inline_limit(source), &mut errors);
+        let report_format = record(parse_or_default(&source.report_format), &mut errors);
+        let policy_parameter_ttl = record(positive("policy_parameter_ttl_seconds", &source.policy_parameter_ttl_seconds, 300), &mut errors);
+
+        let cross_account_role_name = non_empty(&source.cross_account_role_name).map(str::to_string);
+        if !accounts.is_empty() && cross_account_role_name.is_none() {
+            errors.push(invalid_config("cross_account_role_name must be set when accounts are given".to_string()));
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Self {
+            policy: DecisionPolicy {
+                retention_rules: RetentionRules::new(rules, default_retention, log_group_tags),
+                retention_limits,
+                invalid_retention_strategy: strategy,
+                tagging: tagging_policy(source, &retention_tag_keys),
+                retention_tag_keys,
+                exclusions,
+                concurrency,
+            },
+            regions,
+            accounts,
+            cross_account_role_name,
+            report_inline_limit,
+            report_bucket: non_empty(&source.report_bucket).map(str::to_string),
+            report_prefix: non_empty(&source.report_prefix).unwrap_or_default().to_string(),
+            report_format,
+            metric_namespace: non_empty(&source.metric_namespace).unwrap_or("LogRotation").to_string(),
+            policy_parameter_name: non_empty(&source.policy_parameter_name).map(str::to_string),
+            policy_parameter_ttl: Duration::from_secs(policy_parameter_ttl as u64),
+            dry_run: non_empty(&source.dry_run).is_some_and(|dry_run| dry_run.eq_ignore_ascii_case("true")),
+            aws_partition: non_empty(&source.aws_partition).unwrap_or("aws").to_string(),
+        })
+    }
+}
+
+/// Every problem in one error, for callers which can only return one
+pub fn combine(errors: Vec<Error>) -> Error {
+    invalid_config(errors.into_iter().map(|error| error.message).collect::<Vec<_>>().join("; "))
+}
+
+// Records the error, if any, and carries on with the default so that every setting is checked
+fn record<T: Default>(result: Result<T, Error>, errors: &mut Vec<Error>) -> T {
+    result.unwrap_or_else(|error| {
+        errors.push(error);
+        T::default()
+    })
+}
+
+fn non_empty(setting: &Option<String>) -> Option<&str> {
+    setting.as_deref().filter(|value| !value.is_empty())
+}
+
+fn parse_or_default<T: std::str::FromStr<Err = Error> + Default>(setting: &Option<String>) -> Result<T, Error> {
+    non_empty(setting).map_or_else(|| Ok(T::default()), str::parse)
+}
+
+// Unset settings are None
+fn retention(name: &str, setting: &Option<String>, strategy: InvalidRetentionStrategy) -> Result<Option<RetentionDays>, Error> {
+    let retention = match non_empty(setting) {
+        Some(retention) => retention,
+        None => return Ok(None),
+    };
+
+    let retention = retention
+        .parse()
+        .map_err(|e| invalid_config(format!("Could not parse {} `{}` as a number: {}", name, retention, e)))?;
+    Ok(Some(RetentionDays::new(retention, strategy)?))
+}
+
+/// Rules from `log_retention_rules` alone, without the fallback retention and tags
+fn configured_rules(source: &ConfigSource, strategy: InvalidRetentionStrategy) -> Result<Vec<RetentionRule>, Error> {
+    match non_empty(&source.log_retention_rules) {
+        Some(rules) => RetentionRules::parse_rules(rules, strategy),
+        None => Ok(vec![]),
+    }
+}
+
+fn log_group_tags(source: &ConfigSource) -> Result<Option<HashMap<String, String>>, Error> {
+    non_empty(&source.log_group_tags)
+        .map(|tags| serde_json::from_str(tags).map_err(|e| invalid_config(format!("Could not parse log_group_tags `{}`: {}", tags, e))))
+        .transpose()
+}
+
+fn retention_limits(source: &ConfigSource, strategy: InvalidRetentionStrategy) -> Result<RetentionLimits, Error> {
+    let maximum = match retention("max_retention_in_days", &source.max_retention_in_days, strategy)? {
+        Some(retention_in_days) => {
+            let exemptions = match non_empty(&source.max_retention_exemptions) {
+                Some(exemptions) => MaximumRetention::parse_exemptions(exemptions)?,
+                None => vec![],
+            };
+            Some(MaximumRetention::new(retention_in_days, exemptions))
+        }
+        None => None,
+    };
+
+    let minimum = match retention("min_retention_in_days", &source.min_retention_in_days, strategy)? {
+        Some(retention_in_days) => {
+            let prefixes = match non_empty(&source.min_retention_prefixes) {
+                Some(prefixes) => MinimumRetention::parse_prefixes(prefixes)?,
+                None => vec![],
+            };
+            Some(MinimumRetention::new(retention_in_days, prefixes))
+        }
+        None => None,
+    };
+
+    // A group can only be held between the two if the minimum is no higher than the maximum
+    if let (Some(maximum), Some(minimum)) = (&maximum, &minimum) {
+        if maximum.retention_in_days.days() < minimum.retention_in_days.days() {
+            return Err(invalid_config(format!(
+                "max_retention_in_days `{}` must not be less than min_retention_in_days `{}`",
+                maximum.retention_in_days.days(),
+                minimum.retention_in_days.days()
+            )));
+        }
+    }
+
+    Ok(RetentionLimits { maximum, minimum })
+}
+
+fn retention_tag_keys(source: &ConfigSource) -> Result<RetentionTagKeys, Error> {
+    Ok(RetentionTagKeys {
+        key: non_empty(&source.retention_tag_key).unwrap_or(RETENTION_TAG_KEY).to_string(),
+        additional_opt_out_keys: string_list("additional_opt_out_tag_keys", &source.additional_opt_out_tag_keys)?,
+    })
+}
+
+/// The marker is `marker_tag_key`=`marker_tag_value`, under the retention tag key unless `marker_tag_key` is set. `marker_tag_enabled`=`false` turns it off.
+/// Changes are recorded under `previous_retention_tag_key` and `retention_changed_at_tag_key`, rollbacks under `rolled_back_tag_key`.
+fn tagging_policy(source: &ConfigSource, retention_tag_keys: &RetentionTagKeys) -> TaggingPolicy {
+    let marker = match non_empty(&source.marker_tag_enabled).is_some_and(|enabled| enabled.eq_ignore_ascii_case("false")) {
+        true => None,
+        false => Some((
+            non_empty(&source.marker_tag_key).unwrap_or(&retention_tag_keys.key).to_string(),
+            non_empty(&source.marker_tag_value).unwrap_or(MARKER_TAG_VALUE).to_string(),
+        )),
+    };
+    TaggingPolicy {
+        marker,
+        previous_retention_key: non_empty(&source.previous_retention_tag_key).unwrap_or(PREVIOUS_RETENTION_TAG_KEY).to_string(),
+        changed_at_key: non_empty(&source.retention_changed_at_tag_key)
+            .unwrap_or(RETENTION_CHANGED_AT_TAG_KEY)
+            .to_string(),
+        rolled_back_key: non_empty(&source.rolled_back_tag_key).unwrap_or(ROLLED_BACK_AT_TAG_KEY).to_string(),
+    }
+}
+
+/// From a JSON file at `log_group_exclusions_file`, or else JSON in `log_group_exclusions`
+fn exclusions(source: &ConfigSource) -> Result<Exclusions, Error> {
+    if let Some(path) = non_empty(&source.log_group_exclusions_file) {
+        let exclusions = std::fs::read_to_string(path).map_err(|e| invalid_config(format!("Could not read log_group_exclusions_file `{}`: {}", path, e)))?;
+        return Exclusions::parse(&exclusions);
+    }
+
+    match non_empty(&source.log_group_exclusions) {
+        Some(exclusions) => Exclusions::parse(exclusions),
+        None => Ok(Exclusions::default()),
+    }
+}
+
+// A JSON array of strings
+fn string_list(name: &str, setting: &Option<String>) -> Result<Vec<String>, Error> {
+    match non_empty(setting) {
+        Some(list) => serde_json::from_str(list).map_err(|e| invalid_config(format!("Could not parse {} `{}`: {}", name, list, e))),
+        None => Ok(vec![]),
+    }
+}
+
+fn positive(name: &str, setting: &Option<String>, default: usize) -> Result<usize, Error> {
+    match non_empty(setting).map(|value| (value, value.parse())) {
+        None => Ok(default),
+        Some((_, Ok(value))) if value > 0 => Ok(value),
+        Some((value, _)) => Err(invalid_config(format!("{} must be a positive number, not `{}`", name, value))),
+    }
+}
+
+fn report_inline_limit(source: &ConfigSource) -> Result<usize, Error> {
+    match non_empty(&source.report_inline_limit) {
+        Some(limit) => limit
+            .parse()
+            .map_err(|_| invalid_config(format!("report_inline_limit must be a number, not `{}`", limit))),
+        None => Ok(100),
+    }
+}
+
+fn invalid_config(message: String) -> Error {
+    Error {
+        message,
+        severity: Severity::Error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::{error::Severity, report::ReportFormat, retention_days::InvalidRetentionStrategy};
+
+    use super::{Config, ConfigSource};
+
+    fn config(settings: serde_json::Value) -> Result<Config, Vec<String>> {
+        Config::from_source(&ConfigSource::from_json(settings).unwrap()).map_err(|errors| errors.into_iter().map(|error| error.message).collect())
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = config(json!({})).unwrap();
+
+        assert_eq!(30, config.policy.retention_rules.evaluate("/aws/lambda/abc").retention_in_days.days());
+        assert_eq!(None, config.policy.retention_rules.evaluate("/aws/lambda/abc").tags);
+        assert!(config.policy.retention_limits.maximum.is_none());
+        assert!(config.policy.retention_limits.minimum.is_none());
+        assert_eq!(InvalidRetentionStrategy::Reject, config.policy.invalid_retention_strategy);
+        assert_eq!("retention", config.policy.retention_tag_keys.key);
+        assert_eq!(10, config.policy.concurrency);
+        assert!(config.regions.is_empty());
+        assert!(config.accounts.is_empty());
+        assert_eq!(100, config.report_inline_limit);
+        assert_eq!(None, config.report_bucket);
+        assert_eq!(ReportFormat::JsonLines, config.report_format);
+        assert_eq!("LogRotation", config.metric_namespace);
+        assert!(!config.dry_run);
+        assert_eq!("aws", config.aws_partition);
+    }
+
+    #[test]
+    fn test_retention() {
+        let config = config(json!({"log_retention_in_days": "1", "invalid_retention_strategy": "round_up"})).unwrap();
+
+        assert_eq!(1, config.policy.retention_rules.evaluate("/aws/lambda/abc").retention_in_days.days());
+        assert_eq!(InvalidRetentionStrategy::RoundUp, config.policy.invalid_retention_strategy);
+    }
+
+    #[test]
+    fn test_retention_limits() {
+        let config = config(json!({
+            "max_retention_in_days": "365",
+            "max_retention_exemptions": "[\"/audit/*\"]",
+            "min_retention_in_days": "30",
+            "min_retention_prefixes": "[\"/prod/\"]",
+        }))
+        .unwrap();
+
+        let maximum = config.policy.retention_limits.maximum.unwrap();
+        assert_eq!(365, maximum.retention_in_days.days());
+        assert!(maximum.is_exempt("/audit/payments"));
+        let minimum = config.policy.retention_limits.minimum.unwrap();
+        assert_eq!(30, minimum.retention_in_days.days());
+        assert!(!minimum.applies_to("/dev/payments"));
+    }
+
+    #[test]
+    fn test_retention_limits_maximum_below_minimum() {
+        let errors = config(json!({"max_retention_in_days": "30", "min_retention_in_days": "365"})).unwrap_err();
+
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("max_retention_in_days `30` must not be less than min_retention_in_days `365`"));
+
+        let config = config(json!({"max_retention_in_days": "30", "min_retention_in_days": "30"})).unwrap();
+        assert!(config.policy.retention_limits.maximum.is_some());
+        assert!(config.policy.retention_limits.minimum.is_some());
+    }
+
+    #[test]
+    fn test_log_group_tags() {
+        let tags = |settings| config(settings).unwrap().policy.retention_rules.evaluate("/aws/lambda/abc").tags;
+
+        assert_eq!(
+            Some(HashMap::from([("a".to_string(), "b".to_string()), ("c".to_string(), "d".to_string())])),
+            tags(json!({"log_group_tags": "{\"a\": \"b\", \"c\": \"d\"}"}))
+        );
+        assert_eq!(Some(HashMap::new()), tags(json!({"log_group_tags": "{}"})));
+        // Maps in a file are written as they are
+        assert_eq!(
+            Some(HashMap::from([("a".to_string(), "b".to_string())])),
+            tags(json!({"log_group_tags": {"a": "b"}}))
+        );
+    }
+
+    #[test]
+    fn test_log_group_tags_invalid() {
+        let errors = config(json!({"log_group_tags": "true"})).unwrap_err();
+
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("log_group_tags"));
+    }
+
+    #[test]
+    fn test_retention_tag_keys() {
+        let config = config(json!({"retention_tag_key": "org:log-retention", "additional_opt_out_tag_keys": ["org:keep-logs"]})).unwrap();
+
+        assert_eq!("org:log-retention", config.policy.retention_tag_keys.key);
+        assert_eq!(vec!["org:keep-logs".to_string()], config.policy.retention_tag_keys.additional_opt_out_keys);
+        assert_eq!(
+            Some(("org:log-retention".to_string(), "Set by AWS Default Log Retention project.".to_string())),
+            config.policy.tagging.marker
+        );
+    }
+
+    #[test]
+    fn test_marker_disabled() {
+        assert_eq!(None, config(json!({"marker_tag_enabled": false})).unwrap().policy.tagging.marker);
+    }
+
+    #[test]
+    fn test_change_record_tag_keys() {
+        let tagging = config(json!({"marker_tag_enabled": false})).unwrap().policy.tagging;
+        assert_eq!("previous-retention", tagging.previous_retention_key);
+        assert_eq!("retention-changed-at", tagging.changed_at_key);
+        assert_eq!("retention-rolled-back-at", tagging.rolled_back_key);
+
+        let tagging = config(json!({
+            "previous_retention_tag_key": "org:previous-retention",
+            "retention_changed_at_tag_key": "org:retention-changed-at",
+            "rolled_back_tag_key": "org:retention-rolled-back-at"
+        }))
+        .unwrap()
+        .policy
+        .tagging;
+        assert_eq!("org:previous-retention", tagging.previous_retention_key);
+        assert_eq!("org:retention-changed-at", tagging.changed_at_key);
+        assert_eq!("org:retention-rolled-back-at", tagging.rolled_back_key);
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let exclusions = config(json!({"log_group_exclusions": {"prefixes": ["/aws/vendedlogs/"]}}))
+            .unwrap()
+            .policy
+            .exclusions;
+        assert!(exclusions.check("/aws/vendedlogs/abc", "").is_some());
+
+        let errors = config(json!({"log_group_exclusions_file": "/does/not/exist.json"})).unwrap_err();
+        assert!(errors[0].contains("/does/not/exist.json"));
+    }
+
+    #[test]
+    fn test_sweep_settings() {
+        let config = config(json!({
+            "regions": ["us-east-1", "us-west-2"],
+            "accounts": ["123456789012"],
+            "cross_account_role_name": "LogRetention",
+            "concurrency": 4,
+            "report_bucket": "audit-bucket",
+            "report_format": "csv",
+            "dry_run": true,
+            "aws_partition": "aws-cn",
+        }))
+        .unwrap();
+
+        assert_eq!(vec!["us-east-1".to_string(), "us-west-2".to_string()], config.regions);
+        assert_eq!(vec!["123456789012".to_string()], config.accounts);
+        assert_eq!(4, config.policy.concurrency);
+        assert_eq!(Some("audit-bucket".to_string()), config.report_bucket);
+        assert_eq!(ReportFormat::Csv, config.report_format);
+        assert!(config.dry_run);
+        assert_eq!("aws-cn", config.aws_partition);
+    }
+
+    #[test]
+    fn test_collects_every_error() {
+        let errors = config(json!({
+            "log_retention_in_days": "asdasdasd",
+            "log_retention_rules": "[{\"name\": \"no-matcher\", \"retention_in_days\": 1}]",
+            "additional_opt_out_tag_keys": "org:keep-logs",
+            "accounts": ["123456789012"],
+            "concurrency": "0",
+            "report_inline_limit": "lots",
+            "report_format": "xml",
+        }))
+        .unwrap_err();
+
+        assert_eq!(7, errors.len(), "{:?}", errors);
+        for expected in ["asdasdasd", "no-matcher", "org:keep-logs", "cross_account_role_name", "positive", "lots", "xml"] {
+            assert!(
+                errors.iter().any(|error| error.contains(expected)),
+                "No error mentions {}: {:?}",
+                expected,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_json_drops_empty_settings() {
+        let source = ConfigSource::from_json(json!({"log_retention_in_days": "", "report_bucket": null, "unknown": "ignored"})).unwrap();
+
+        assert_eq!(ConfigSource::default(), source);
+        assert_eq!(Severity::Error, ConfigSource::from_json(json!(["not", "a", "map"])).unwrap_err().severity);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = std::env::temp_dir();
+        let files = [
+            ("config.json", r#"{"log_retention_in_days": 14, "regions": ["us-east-1"]}"#),
+            ("config.yaml", "log_retention_in_days: 14\nregions:\n  - us-east-1\n"),
+            ("config.toml", "log_retention_in_days = 14\nregions = [\"us-east-1\"]\n"),
+        ];
+
+        for (name, contents) in files {
+            let path = dir.join(format!("log-retention-test-{}", name));
+            std::fs::write(&path, contents).unwrap();
+            let source = ConfigSource::from_file(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(Some("14".to_string()), source.log_retention_in_days, "{}", name);
+            assert_eq!(Some(r#"["us-east-1"]"#.to_string()), source.regions, "{}", name);
+        }
+
+        assert!(ConfigSource::from_file("config.ini").unwrap_err().message.contains(".toml"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::SystemTime;
+
+use aws_credential_types::Credentials;
+
+use crate::{
+    error::{Error, Severity},
+    sts_traits::AssumeRole,
+};
+
+/// Shows up in the target account's CloudTrail as the session name of the assumed role
+pub const ROLE_SESSION_NAME: &str = "default-log-retention";
+
+pub fn role_arn(partition: &str, account_id: &str, role_name: &str) -> String {
+    format!("arn:{}:iam::{}:role/{}", partition, account_id, role_name)
+}
+
+/// Assumes `role_arn` and returns credentials for building clients in the target account
+pub async fn assume_role(sts: &impl AssumeRole, role_arn: &str) -> Result<Credentials, Error> {
+    let output = sts.assume_role(role_arn, ROLE_SESSION_NAME).await?;
+    let credentials = output.credentials().ok_or_else(|| Error {
+        message: format!("AssumeRole into {} returned no credentials", role_arn),
+        severity: Severity::Error,
+    })?;
+
+    Ok(Credentials::new(
+        credentials.access_key_id(),
+        credentials.secret_access_key(),
+        Some(credentials.session_token().to_string()),
+        SystemTime::try_from(*credentials.expiration()).ok(),
+        "AssumeRole",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_sts::{
+        operation::assume_role::AssumeRoleOutput,
+        types::{error::ExpiredTokenException, Credentials as StsCredentials},
+        Error as StsError,
+    };
+    use aws_smithy_types::DateTime;
+    use mockall::predicate;
+
+    use crate::sts_traits::MockAssumeRole;
+
+    use super::{assume_role, role_arn, ROLE_SESSION_NAME};
+
+    #[test]
+    fn test_role_arn() {
+        assert_eq!("arn:aws:iam::123456789012:role/LogRetention", role_arn("aws", "123456789012", "LogRetention"));
+    }
+
+    #[tokio::test]
+    async fn test_assume_role() {
+        let mut mock_sts = MockAssumeRole::new();
+        mock_sts
+            .expect_assume_role()
+            .with(predicate::eq("arn:aws:iam::123456789012:role/LogRetention"), predicate::eq(ROLE_SESSION_NAME))
+            .once()
+            .returning(|_, _| {
+                let credentials = StsCredentials::builder()
+                    .access_key_id("AKIAEXAMPLE")
+                    .secret_access_key("secret")
+                    .session_token("token")
+                    .expiration(DateTime::from_secs(1_700_000_000))
+                    .build()
+                    .unwrap();
+                Ok(AssumeRoleOutput::builder().credentials(credentials).build())
+            });
+
+        let credentials = assume_role(&mock_sts, "arn:aws:iam::123456789012:role/LogRetention").await.unwrap();
+
+        assert_eq!("AKIAEXAMPLE", credentials.access_key_id());
+        assert_eq!(Some("token"), credentials.session_token());
+    }
+
+    #[tokio::test]
+    async fn test_assume_role_fails() {
+        let mut mock_sts = MockAssumeRole::new();
+        mock_sts
+            .expect_assume_role()
+            .returning(|_, _| Err(StsError::ExpiredTokenException(ExpiredTokenException::builder().build())));
+
+        let error = assume_role(&mock_sts, "arn:aws:iam::123456789012:role/LogRetention").await.unwrap_err();
+
+        assert!(error.message.contains("ExpiredToken"));
+    }
+
+    #[tokio::test]
+    async fn test_assume_role_without_credentials() {
+        let mut mock_sts = MockAssumeRole::new();
+        mock_sts.expect_assume_role().returning(|_, _| Ok(AssumeRoleOutput::builder().build()));
+
+        let error = assume_role(&mock_sts, "arn:aws:iam::123456789012:role/LogRetention").await.unwrap_err();
+
+        assert!(error.message.contains("returned no credentials"));
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::{retention_days::RetentionDays, retention_limits::LimitAdjustment, retention_rules::RuleMatch};
+
+/// Dry run is enabled by the `dry_run` setting (`configured`) or by `"dryRun": true` in the invocation payload
+pub fn is_dry_run(payload: &JsonValue, configured: bool) -> bool {
+    configured || payload.get("dryRun").and_then(JsonValue::as_bool).unwrap_or(false)
+}
+
+/// What would happen to a log group. Returned and logged instead of making changes in dry-run mode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedAction {
+    pub log_group_name: String,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum Action {
+    #[serde(rename_all = "camelCase")]
+    SetRetention {
+        retention_in_days: RetentionDays,
+        rule: String,
+        tags: HashMap<String, String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    ReduceToMaximum {
+        previous_retention_in_days: i32,
+        retention_in_days: RetentionDays,
+    },
+    #[serde(rename_all = "camelCase")]
+    RaiseToMinimum {
+        previous_retention_in_days: i32,
+        retention_in_days: RetentionDays,
+    },
+    Skip {
+        reason: String,
+    },
+}
+
+impl PlannedAction {
+    /// `tags` are every tag the group would be tagged with, see `RetentionDecision::tags`
+    pub fn set_retention(log_group_name: impl Into<String>, rule_match: &RuleMatch, tags: HashMap<String, String>) -> Self {
+        Self {
+            log_group_name: log_group_name.into(),
+            action: Action::SetRetention {
+                retention_in_days: rule_match.retention_in_days,
+                rule: rule_match.rule.clone(),
+                tags,
+            },
+        }
+    }
+
+    pub fn enforce_limit(log_group_name: impl Into<String>, previous_retention_in_days: i32, adjustment: LimitAdjustment) -> Self {
+        let action = match adjustment {
+            LimitAdjustment::ReducedToMaximum(retention_in_days) => Action::ReduceToMaximum {
+                previous_retention_in_days,
+                retention_in_days,
+            },
+            LimitAdjustment::RaisedToMinimum(retention_in_days) => Action::RaiseToMinimum {
+                previous_retention_in_days,
+                retention_in_days,
+            },
+        };
+        Self {
+            log_group_name: log_group_name.into(),
+            action,
+        }
+    }
+
+    pub fn skip(log_group_name: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            log_group_name: log_group_name.into(),
+            action: Action::Skip { reason: reason.into() },
+        }
+    }
+}
+
+impl Display for PlannedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.action {
+            Action::SetRetention { retention_in_days, rule, tags } => {
+                write!(f, "Would set {} days on {} (rule `{}`)", retention_in_days, self.log_group_name, rule)?;
+                match tags.is_empty() {
+                    true => write!(f, "."),
+                    false => write!(f, " and tag it with {:?}.", tags),
+                }
+            }
+            Action::ReduceToMaximum {
+                previous_retention_in_days,
+                retention_in_days,
+            } => write!(
+                f,
+                "Would reduce {} from {} to {} days (maximum retention).",
+                self.log_group_name, previous_retention_in_days, retention_in_days
+            ),
+            Action::RaiseToMinimum {
+                previous_retention_in_days,
+                retention_in_days,
+            } => write!(
+                f,
+                "Would raise {} from {} to {} days (minimum retention).",
+                self.log_group_name, previous_retention_in_days, retention_in_days
+            ),
+            Action::Skip { reason } => write!(f, "Would skip {} because {}.", self.log_group_name, reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use std::collections::HashMap;
+
+    use crate::{retention_days::RetentionDays, retention_limits::LimitAdjustment, retention_rules::RuleMatch};
+
+    use super::{is_dry_run, PlannedAction};
+
+    #[test]
+    fn test_is_dry_run_from_payload() {
+        assert!(is_dry_run(&json!({"dryRun": true}), false));
+        assert!(!is_dry_run(&json!({"dryRun": false}), false));
+        assert!(!is_dry_run(&json!({}), false));
+        assert!(is_dry_run(&json!({"dryRun": false}), true));
+    }
+
+    #[test]
+    fn test_display() {
+        let rule_match = RuleMatch {
+            rule: "lambda".to_string(),
+            retention_in_days: RetentionDays::try_from(14).unwrap(),
+            tags: None,
+        };
+
+        assert_eq!(
+            "Would set 14 days on /aws/lambda/abc (rule `lambda`).",
+            PlannedAction::set_retention("/aws/lambda/abc", &rule_match, HashMap::new()).to_string()
+        );
+        assert_eq!(
+            "Would skip abc because it has retention already.",
+            PlannedAction::skip("abc", "it has retention already").to_string()
+        );
+        assert_eq!(
+            "Would reduce abc from 3653 to 365 days (maximum retention).",
+            PlannedAction::enforce_limit("abc", 3653, LimitAdjustment::ReducedToMaximum(RetentionDays::try_from(365).unwrap())).to_string()
+        );
+    }
+
+    #[test]
+    fn test_serialize_enforce_limit() {
+        let plan = PlannedAction::enforce_limit("abc", 1, LimitAdjustment::RaisedToMinimum(RetentionDays::try_from(30).unwrap()));
+
+        assert_eq!(
+            json!({"logGroupName": "abc", "action": "raiseToMinimum", "previousRetentionInDays": 1, "retentionInDays": 30}),
+            serde_json::to_value(plan).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let plan = PlannedAction::skip("abc", "it has retention already");
+
+        assert_eq!(
+            json!({"logGroupName": "abc", "action": "skip", "reason": "it has retention already"}),
+            serde_json::to_value(plan).unwrap()
+        );
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt::Display;
 use serde::Serialize;
 
 use aws_sdk_cloudwatchlogs::Error as CloudWatchLogsError;
+use aws_sdk_sts::Error as StsError;
 
 #[derive(Debug, Serialize)]
 pub struct Error {
@@ -32,3 +33,12 @@ impl From<CloudWatchLogsError> for Error {
         }
     }
 }
+
+impl From<StsError> for Error {
+    fn from(e: StsError) -> Self {
+        Self {
+            message: e.to_string(),
+            severity: Severity::Error,
+        }
+    }
+}
@@ -1,40 +1,455 @@
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    error::{Error, Severity},
+    sweep::SweepTarget,
+};
+
+/// Any payload the log retention Lambda can be invoked with. Wrapped events are unwrapped by `into_requests`.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum InvocationEvent {
+    Sqs(SqsEvent),
+    Sns(SnsEvent),
+    /// An SNS notification delivered to SQS without raw message delivery
+    SnsNotification(SnsMessage),
+    EventBridge(CloudTrailEvent),
+    Direct(DirectInvocation),
+}
+
+/// An EventBridge event. The envelope fields are preferred over the CloudTrail `detail` so events not shaped like CloudTrail can be used.
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct CloudTrailEvent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<String>,
     pub detail: CloudTrailEventDetail,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct CloudTrailEventDetail {
-    pub aws_region: String,
-    pub user_identity: CloudTrailEventUserIdentity,
-    pub request_parameters: CloudTrailEventRequestParameters,
+    /// Missing for events which are not from CloudTrail, which are taken to be about a new log group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aws_region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_identity: Option<CloudTrailEventUserIdentity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_parameters: Option<CloudTrailEventRequestParameters>,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct CloudTrailEventRequestParameters {
-    pub log_group_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_group_name: Option<String>,
+    /// `TagResource` and `UntagResource` name the log group by ARN
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_arn: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct CloudTrailEventUserIdentity {
+    pub account_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SqsEvent {
+    #[serde(rename = "Records")]
+    pub records: Vec<SqsRecord>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SqsRecord {
+    pub message_id: String,
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SnsEvent {
+    #[serde(rename = "Records")]
+    pub records: Vec<SnsRecord>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SnsRecord {
+    #[serde(rename = "Sns")]
+    pub sns: SnsMessage,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SnsMessage {
+    #[serde(rename = "Message")]
+    pub message: String,
+}
+
+/// Invoking the Lambda with just a log group name, which is taken to be in the Lambda's own account and region
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectInvocation {
+    pub log_group_name: String,
+}
+
+/// A log group to set retention on, whatever event it came from
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogGroupRequest {
     pub account_id: String,
+    pub region: String,
+    pub log_group_name: String,
+    #[serde(default)]
+    pub trigger: Trigger,
+}
+
+/// The API call an event is about
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Trigger {
+    #[default]
+    CreateLogGroup,
+    /// Retention was changed, so the group is re-evaluated against retention tags and limits
+    PutRetentionPolicy,
+    /// Retention was removed, so the default is re-applied
+    DeleteRetentionPolicy,
+    /// Tags were added or changed, so a `retention` tag may now apply. Also covers the deprecated `TagLogGroup`.
+    TagResource,
+    /// Tags were removed, so a `retention` tag may no longer apply. Also covers the deprecated `UntagLogGroup`.
+    UntagResource,
+}
+
+impl FromStr for Trigger {
+    type Err = Error;
+
+    fn from_str(event_name: &str) -> Result<Self, Self::Err> {
+        match event_name {
+            "CreateLogGroup" => Ok(Trigger::CreateLogGroup),
+            "PutRetentionPolicy" => Ok(Trigger::PutRetentionPolicy),
+            "DeleteRetentionPolicy" => Ok(Trigger::DeleteRetentionPolicy),
+            "TagResource" | "TagLogGroup" => Ok(Trigger::TagResource),
+            "UntagResource" | "UntagLogGroup" => Ok(Trigger::UntagResource),
+            _ => Err(Error {
+                message: format!("Ignoring `{}` event, which does not affect log group retention.", event_name),
+                severity: Severity::Warning,
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
 }
 
 impl CloudTrailEvent {
     pub fn new(account_id: impl Into<String>, aws_region: impl Into<String>, log_group_name: impl Into<String>) -> Self {
         Self {
+            account: None,
+            region: None,
+            resources: vec![],
             detail: CloudTrailEventDetail {
-                request_parameters: CloudTrailEventRequestParameters {
-                    log_group_name: log_group_name.into(),
-                },
-                aws_region: aws_region.into(),
-                user_identity: CloudTrailEventUserIdentity { account_id: account_id.into() },
+                event_name: None,
+                request_parameters: Some(CloudTrailEventRequestParameters {
+                    log_group_name: Some(log_group_name.into()),
+                    resource_arn: None,
+                }),
+                aws_region: Some(aws_region.into()),
+                user_identity: Some(CloudTrailEventUserIdentity {
+                    account_id: Some(account_id.into()),
+                }),
             },
         }
     }
+
+    pub fn with_event_name(mut self, event_name: impl Into<String>) -> Self {
+        self.detail.event_name = Some(event_name.into());
+        self
+    }
+
+    fn into_request(self) -> Result<LogGroupRequest, Error> {
+        let trigger = match &self.detail.event_name {
+            Some(event_name) => event_name.parse()?,
+            None => Trigger::default(),
+        };
+
+        let (log_group_name, resource_arn) = match self.detail.request_parameters {
+            Some(request_parameters) => (request_parameters.log_group_name, request_parameters.resource_arn),
+            None => (None, None),
+        };
+
+        // A log group ARN in `resources` or `requestParameters.resourceArn` has everything needed on its own.
+        // ARNs of other resources, e.g. a `TagResource` event for a destination, are ignored.
+        let from_resources = resource_arn.iter().chain(&self.resources).find_map(|arn| LogGroupRequest::from_arn(arn));
+
+        let log_group_name = log_group_name.or_else(|| from_resources.as_ref().map(|request| request.log_group_name.clone()));
+        let account_id = self
+            .account
+            .or_else(|| self.detail.user_identity.and_then(|user_identity| user_identity.account_id))
+            .or_else(|| from_resources.as_ref().map(|request| request.account_id.clone()));
+        let region = self.region.or(self.detail.aws_region).or_else(|| from_resources.map(|request| request.region));
+
+        match (account_id, region, log_group_name) {
+            (Some(account_id), Some(region), Some(log_group_name)) => Ok(LogGroupRequest::new(account_id, region, log_group_name).with_trigger(trigger)),
+            _ => Err(Error {
+                message: "Could not find the log group name, account and region in the event.".to_string(),
+                severity: Severity::Warning,
+            }),
+        }
+    }
+}
+
+impl InvocationEvent {
+    /// Parses a message wrapped by SQS or SNS
+    pub fn from_message(message: &str) -> Result<Self, Error> {
+        serde_json::from_str(message).map_err(|error| Error {
+            message: format!("Error deserializing wrapped message `{}`. Error: `{}`.", message, error),
+            severity: Severity::Warning,
+        })
+    }
+
+    /// Unwraps the event into the log groups it is about
+    ///
+    /// # Arguments
+    ///
+    /// * `home` the Lambda's own account and region, used for direct invocations
+    pub fn into_requests(self, home: &SweepTarget) -> Result<Vec<LogGroupRequest>, Error> {
+        match self {
+            InvocationEvent::Sqs(sqs_event) => flatten(sqs_event.records.into_iter().map(|record| record.body), home),
+            InvocationEvent::Sns(sns_event) => flatten(sns_event.records.into_iter().map(|record| record.sns.message), home),
+            InvocationEvent::SnsNotification(sns_message) => flatten([sns_message.message], home),
+            InvocationEvent::EventBridge(event) => Ok(vec![event.into_request()?]),
+            InvocationEvent::Direct(DirectInvocation { log_group_name }) => match (&home.account_id, &home.region) {
+                (Some(account_id), Some(region)) => Ok(vec![LogGroupRequest::new(account_id, region, log_group_name)]),
+                _ => Err(Error {
+                    message: format!("Could not determine the account and region of log group {}.", log_group_name),
+                    severity: Severity::Warning,
+                }),
+            },
+        }
+    }
+}
+
+/// Parses each wrapped message as an event of its own
+fn flatten(messages: impl IntoIterator<Item = String>, home: &SweepTarget) -> Result<Vec<LogGroupRequest>, Error> {
+    let mut requests = vec![];
+    for message in messages {
+        requests.extend(InvocationEvent::from_message(&message)?.into_requests(home)?);
+    }
+    Ok(requests)
+}
+
+impl LogGroupRequest {
+    pub fn new(account_id: impl Into<String>, region: impl Into<String>, log_group_name: impl Into<String>) -> Self {
+        Self {
+            account_id: account_id.into(),
+            region: region.into(),
+            log_group_name: log_group_name.into(),
+            trigger: Trigger::default(),
+        }
+    }
+
+    pub fn with_trigger(mut self, trigger: Trigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    /// Parses `arn:<partition>:logs:<region>:<account>:log-group:<name>`, with or without the trailing `:*`
+    pub fn from_arn(arn: &str) -> Option<Self> {
+        let parts: Vec<&str> = arn.splitn(7, ':').collect();
+        match parts.as_slice() {
+            ["arn", _, "logs", region, account_id, "log-group", log_group_name] if !region.is_empty() && !account_id.is_empty() => {
+                Some(Self::new(*account_id, *region, log_group_name.trim_end_matches(":*")))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn log_group_arn(&self, partition: &str) -> String {
+        format!("arn:{}:logs:{}:{}:log-group:{}", partition, self.region, self.account_id, self.log_group_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{error::Severity, sweep::SweepTarget};
+
+    use super::{CloudTrailEvent, InvocationEvent, LogGroupRequest, Trigger};
+
+    fn home() -> SweepTarget {
+        SweepTarget {
+            account_id: Some("999".to_string()),
+            region: Some("us-west-2".to_string()),
+        }
+    }
+
+    fn requests(payload: serde_json::Value) -> Vec<LogGroupRequest> {
+        serde_json::from_value::<InvocationEvent>(payload)
+            .expect("Should deserialize")
+            .into_requests(&home())
+            .expect("Should normalize")
+    }
+
+    #[test]
+    fn test_cloud_trail_event() {
+        let event = json!(CloudTrailEvent::new("123", "us-east-1", "MyLogGroup"));
+
+        assert_eq!(vec![LogGroupRequest::new("123", "us-east-1", "MyLogGroup")], requests(event));
+    }
+
+    #[test]
+    fn test_retention_events() {
+        let put = json!(CloudTrailEvent::new("123", "us-east-1", "MyLogGroup").with_event_name("PutRetentionPolicy"));
+        let delete = json!(CloudTrailEvent::new("123", "us-east-1", "MyLogGroup").with_event_name("DeleteRetentionPolicy"));
+
+        assert_eq!(Trigger::PutRetentionPolicy, requests(put)[0].trigger);
+        assert_eq!(Trigger::DeleteRetentionPolicy, requests(delete)[0].trigger);
+    }
+
+    #[test]
+    fn test_tag_events() {
+        let tag = json!({"detail": {
+            "eventName": "TagResource",
+            "awsRegion": "us-east-1",
+            "userIdentity": {"accountId": "123"},
+            "requestParameters": {"resourceArn": "arn:aws:logs:us-east-1:123:log-group:MyLogGroup", "tags": {"retention": "14"}}
+        }});
+        let untag = json!({"detail": {
+            "eventName": "UntagLogGroup",
+            "awsRegion": "us-east-1",
+            "userIdentity": {"accountId": "123"},
+            "requestParameters": {"logGroupName": "MyLogGroup", "tags": ["retention"]}
+        }});
+
+        let expected = LogGroupRequest::new("123", "us-east-1", "MyLogGroup");
+        assert_eq!(vec![expected.clone().with_trigger(Trigger::TagResource)], requests(tag));
+        assert_eq!(vec![expected.with_trigger(Trigger::UntagResource)], requests(untag));
+    }
+
+    #[test]
+    fn test_tag_events_for_other_resources_are_warnings() {
+        let event: InvocationEvent = serde_json::from_value(json!({"detail": {
+            "eventName": "TagResource",
+            "awsRegion": "us-east-1",
+            "userIdentity": {"accountId": "123"},
+            "requestParameters": {"resourceArn": "arn:aws:logs:us-east-1:123:destination:MyDestination"}
+        }}))
+        .unwrap();
+
+        let error = event.into_requests(&home()).unwrap_err();
+
+        assert_eq!(Severity::Warning, error.severity);
+    }
+
+    #[test]
+    fn test_other_events_are_warnings() {
+        let event: InvocationEvent =
+            serde_json::from_value(json!(CloudTrailEvent::new("123", "us-east-1", "MyLogGroup").with_event_name("DeleteLogGroup"))).unwrap();
+
+        let error = event.into_requests(&home()).unwrap_err();
+
+        assert_eq!(Severity::Warning, error.severity);
+    }
+
+    #[test]
+    fn test_envelope_preferred_over_detail() {
+        let event = json!({
+            "account": "123",
+            "region": "us-east-1",
+            "detail": {"awsRegion": "us-east-2", "userIdentity": {"accountId": "456"}, "requestParameters": {"logGroupName": "MyLogGroup"}}
+        });
+
+        assert_eq!(vec![LogGroupRequest::new("123", "us-east-1", "MyLogGroup")], requests(event));
+    }
+
+    #[test]
+    fn test_log_group_from_resources() {
+        let event = json!({
+            "account": "123",
+            "region": "us-east-1",
+            "resources": ["arn:aws:logs:us-east-1:123:log-group:/aws/lambda/my-function:*"],
+            "detail": {}
+        });
+
+        assert_eq!(vec![LogGroupRequest::new("123", "us-east-1", "/aws/lambda/my-function")], requests(event));
+    }
+
+    #[test]
+    fn test_direct_invocation() {
+        let event = json!({"logGroupName": "MyLogGroup", "dryRun": true});
+
+        assert_eq!(vec![LogGroupRequest::new("999", "us-west-2", "MyLogGroup")], requests(event));
+    }
+
+    #[test]
+    fn test_direct_invocation_without_home_is_warning() {
+        let event: InvocationEvent = serde_json::from_value(json!({"logGroupName": "MyLogGroup"})).unwrap();
+
+        let error = event.into_requests(&SweepTarget::default()).unwrap_err();
+
+        assert_eq!(Severity::Warning, error.severity);
+    }
+
+    #[test]
+    fn test_sqs_wrapped() {
+        let event = json!({"Records": [
+            {"messageId": "1", "body": json!(CloudTrailEvent::new("123", "us-east-1", "First")).to_string()},
+            {"messageId": "2", "body": json!({"logGroupName": "Second"}).to_string()},
+        ]});
+
+        assert_eq!(
+            vec![
+                LogGroupRequest::new("123", "us-east-1", "First"),
+                LogGroupRequest::new("999", "us-west-2", "Second")
+            ],
+            requests(event)
+        );
+    }
+
+    #[test]
+    fn test_sns_wrapped() {
+        let message = json!(CloudTrailEvent::new("123", "us-east-1", "MyLogGroup")).to_string();
+        let event = json!({"Records": [{"EventSource": "aws:sns", "Sns": {"Message": message}}]});
+
+        assert_eq!(vec![LogGroupRequest::new("123", "us-east-1", "MyLogGroup")], requests(event));
+    }
+
+    #[test]
+    fn test_sns_to_sqs_wrapped() {
+        let notification = json!({"Type": "Notification", "Message": json!({"logGroupName": "MyLogGroup"}).to_string()});
+        let event = json!({"Records": [{"messageId": "1", "body": notification.to_string()}]});
+
+        assert_eq!(vec![LogGroupRequest::new("999", "us-west-2", "MyLogGroup")], requests(event));
+    }
+
+    #[test]
+    fn test_missing_log_group_is_warning() {
+        let event: InvocationEvent = serde_json::from_value(json!({"account": "123", "region": "us-east-1", "detail": {}})).unwrap();
+
+        let error = event.into_requests(&home()).unwrap_err();
+
+        assert_eq!(Severity::Warning, error.severity);
+    }
+
+    #[test]
+    fn test_from_arn() {
+        assert_eq!(
+            Some(LogGroupRequest::new("123", "us-east-1", "/aws/lambda/my-function")),
+            LogGroupRequest::from_arn("arn:aws-us-gov:logs:us-east-1:123:log-group:/aws/lambda/my-function")
+        );
+        assert_eq!(None, LogGroupRequest::from_arn("arn:aws:lambda:us-east-1:123:function:my-function"));
+        assert_eq!(
+            "arn:aws:logs:us-east-1:123:log-group:MyLogGroup",
+            LogGroupRequest::new("123", "us-east-1", "MyLogGroup").log_group_arn("aws")
+        );
+    }
 }
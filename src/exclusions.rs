@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::error::{Error, Severity};
+
+/// Log groups neither Lambda touches. Checked before any API call is made for a log group.
+#[derive(Debug, Clone, Default)]
+pub struct Exclusions {
+    prefixes: Vec<String>,
+    regexes: Vec<Regex>,
+    names: HashSet<String>,
+    /// Without the trailing `:*`, for excluding a group in only one account or region
+    arns: HashSet<String>,
+}
+
+// Shape of the exclusions as they are written in configuration. Terraform's `jsonencode` emits `null` for unset optional attributes.
+#[derive(Debug, Deserialize)]
+struct RawExclusions {
+    prefixes: Option<Vec<String>>,
+    regexes: Option<Vec<String>>,
+    /// Log group names or ARNs
+    names: Option<Vec<String>>,
+}
+
+impl Exclusions {
+    /// Parses `{"prefixes": [...], "regexes": [...], "names": [...]}`, where `names` may hold log group names or ARNs
+    pub fn parse(exclusions: &str) -> Result<Self, Error> {
+        let raw: RawExclusions =
+            serde_json::from_str(exclusions).map_err(|e| invalid_exclusions(format!("Could not parse exclusions `{}`: {}", exclusions, e)))?;
+
+        let regexes = raw
+            .regexes
+            .unwrap_or_default()
+            .iter()
+            .map(|regex| Regex::new(regex).map_err(|e| invalid_exclusions(format!("Exclusion regex `{}` is invalid: {}", regex, e))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (arns, names): (Vec<String>, Vec<String>) = raw.names.unwrap_or_default().into_iter().partition(|name| name.starts_with("arn:"));
+
+        Ok(Self {
+            prefixes: raw.prefixes.unwrap_or_default(),
+            regexes,
+            names: names.into_iter().collect(),
+            arns: arns.iter().map(|arn| arn.trim_end_matches(":*").to_string()).collect(),
+        })
+    }
+
+    /// Returns why the log group is excluded, if it is
+    pub fn check(&self, log_group_name: &str, log_group_arn: &str) -> Option<String> {
+        if self.names.contains(log_group_name) {
+            return Some("it is excluded by name".to_string());
+        }
+        if self.arns.contains(log_group_arn.trim_end_matches(":*")) {
+            return Some("it is excluded by ARN".to_string());
+        }
+        if let Some(prefix) = self.prefixes.iter().find(|prefix| log_group_name.starts_with(prefix.as_str())) {
+            return Some(format!("it is excluded by prefix `{}`", prefix));
+        }
+        self.regexes
+            .iter()
+            .find(|regex| regex.is_match(log_group_name))
+            .map(|regex| format!("it is excluded by regex `{}`", regex))
+    }
+}
+
+fn invalid_exclusions(message: String) -> Error {
+    Error {
+        message,
+        severity: Severity::Error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Severity;
+
+    use super::Exclusions;
+
+    const ARN: &str = "arn:aws:logs:us-east-1:123:log-group";
+
+    #[test]
+    fn test_check() {
+        let exclusions = Exclusions::parse(
+            r#"{"prefixes": ["/aws/vendedlogs/"], "regexes": ["^/ecs/.*-canary$"], "names": ["/audit/payments", "arn:aws:logs:us-east-1:123:log-group:/shared/app:*"]}"#,
+        )
+        .unwrap();
+
+        let check = |name: &str| exclusions.check(name, &format!("{}:{}:*", ARN, name));
+
+        assert_eq!(
+            Some("it is excluded by prefix `/aws/vendedlogs/`".to_string()),
+            check("/aws/vendedlogs/states/abc")
+        );
+        assert_eq!(Some("it is excluded by regex `^/ecs/.*-canary$`".to_string()), check("/ecs/web-canary"));
+        assert_eq!(Some("it is excluded by name".to_string()), check("/audit/payments"));
+        assert_eq!(Some("it is excluded by ARN".to_string()), check("/shared/app"));
+        assert_eq!(None, check("/ecs/web"));
+        assert_eq!(None, exclusions.check("/shared/app", "arn:aws:logs:us-west-2:123:log-group:/shared/app"));
+    }
+
+    #[test]
+    fn test_parse_allows_nulls() {
+        let exclusions = Exclusions::parse(r#"{"prefixes": null, "regexes": null, "names": ["/audit/payments"]}"#).unwrap();
+
+        assert!(exclusions.check("/audit/payments", &format!("{}:/audit/payments", ARN)).is_some());
+        assert!(Exclusions::default().check("/audit/payments", "").is_none());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_regex() {
+        let error = Exclusions::parse(r#"{"regexes": ["("]}"#).unwrap_err();
+
+        assert_eq!(Severity::Error, error.severity);
+    }
+}
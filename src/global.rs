@@ -1,7 +1,11 @@
-use std::{collections::HashMap, time::Duration};
-
-use aws_config::{BehaviorVersion, SdkConfig};
-use aws_sdk_cloudwatchlogs::Client as CloudWatchLogsClient;
+use std::time::Duration;
+
+use aws_config::{BehaviorVersion, ConfigLoader, SdkConfig};
+use aws_credential_types::Credentials;
+use aws_sdk_cloudwatchlogs::{config::Region, Client as CloudWatchLogsClient};
+use aws_sdk_s3::Client as S3Client;
+use aws_sdk_ssm::Client as SsmClient;
+use aws_sdk_sts::Client as StsClient;
 use aws_smithy_types::retry::{RetryConfig, RetryMode};
 use cached::proc_macro::cached;
 use log::trace;
@@ -9,6 +13,9 @@ use metrics_cloudwatch_embedded::Collector;
 use tracing::info_span;
 
 use crate::cloudwatch_logs_traits::CloudWatchLogs;
+use crate::s3_traits::S3;
+use crate::ssm_traits::Ssm;
+use crate::sts_traits::Sts;
 
 #[cached]
 pub async fn cloudwatch_logs() -> CloudWatchLogs {
@@ -16,38 +23,61 @@ pub async fn cloudwatch_logs() -> CloudWatchLogs {
     CloudWatchLogs::new(CloudWatchLogsClient::new(&sdk_config))
 }
 
-#[cached]
-async fn sdk_config() -> SdkConfig {
-    let retry_config = RetryConfig::standard()
-        .with_initial_backoff(Duration::from_millis(500))
-        .with_max_attempts(10)
-        .with_retry_mode(RetryMode::Adaptive);
+/// Client for another region and/or account, built from the same SDK configuration. Used by the multi-region and cross-account sweeps.
+pub async fn cloudwatch_logs_for(region: Option<String>, credentials: Option<Credentials>) -> CloudWatchLogs {
+    let sdk_config = sdk_config().await;
+    let mut config = aws_sdk_cloudwatchlogs::config::Builder::from(&sdk_config);
+    if let Some(region) = region {
+        config = config.region(Region::new(region));
+    }
+    if let Some(credentials) = credentials {
+        config = config.credentials_provider(credentials);
+    }
+    CloudWatchLogs::new(CloudWatchLogsClient::from_conf(config.build()))
+}
+
+/// Client for running outside Lambda, e.g. from the CLI, with an optional named profile and region instead of the environment's
+pub async fn cloudwatch_logs_for_profile(profile: Option<String>, region: Option<String>) -> CloudWatchLogs {
+    let mut loader = config_loader();
+    if let Some(profile) = profile {
+        loader = loader.profile_name(profile);
+    }
+    if let Some(region) = region {
+        loader = loader.region(Region::new(region));
+    }
+    CloudWatchLogs::new(CloudWatchLogsClient::new(&loader.load().await))
+}
 
-    aws_config::defaults(BehaviorVersion::v2023_11_09()).retry_config(retry_config).load().await
+#[cached]
+pub async fn sts() -> Sts {
+    let sdk_config = sdk_config().await;
+    Sts::new(StsClient::new(&sdk_config))
 }
 
-#[cfg_attr(not(test), cached)] // Disables caching for tests https://github.com/jaemk/cached/issues/130
-pub fn retention() -> i32 {
-    std::env::var("log_retention_in_days")
-        .unwrap_or_else(|_| "30".to_string())
-        .parse()
-        .unwrap_or(30)
+#[cached]
+pub async fn s3() -> S3 {
+    let sdk_config = sdk_config().await;
+    S3::new(S3Client::new(&sdk_config))
 }
 
-#[cfg_attr(not(test), cached)] // Disables caching for tests https://github.com/jaemk/cached/issues/130
-pub fn log_group_tags() -> Option<HashMap<String, String>> {
-    let log_group_tags = std::env::var("log_group_tags").ok()?;
-    let log_group_tags = serde_json::from_str(&log_group_tags).ok()?;
-    Some(log_group_tags)
+#[cached]
+pub async fn ssm() -> Ssm {
+    let sdk_config = sdk_config().await;
+    Ssm::new(SsmClient::new(&sdk_config))
 }
 
 #[cached]
-pub fn metric_namespace() -> String {
-    std::env::var("metric_namespace").unwrap_or_else(|_| "LogRotation".to_string())
+async fn sdk_config() -> SdkConfig {
+    config_loader().load().await
 }
 
-pub fn aws_partition() -> String {
-    std::env::var("aws_partition").unwrap_or_else(|_| "aws".to_string())
+fn config_loader() -> ConfigLoader {
+    let retry_config = RetryConfig::standard()
+        .with_initial_backoff(Duration::from_millis(500))
+        .with_max_attempts(10)
+        .with_retry_mode(RetryMode::Adaptive);
+
+    aws_config::defaults(BehaviorVersion::v2023_11_09()).retry_config(retry_config)
 }
 
 pub fn initialize_logger() {
@@ -55,14 +85,14 @@ pub fn initialize_logger() {
     env_logger::builder().format_timestamp(None).init();
 }
 
-pub fn initialize_metrics() -> &'static Collector {
+pub fn initialize_metrics(metric_namespace: &str) -> &'static Collector {
     trace!("Initializing metrics emitter...");
 
     let lambda_function_name =
         std::env::var("AWS_LAMBDA_FUNCTION_NAME").expect("Could not determine Lambda function name. Is this code being run in AWS Lambda?");
 
     metrics_cloudwatch_embedded::Builder::new()
-        .cloudwatch_namespace(metric_namespace())
+        .cloudwatch_namespace(metric_namespace.to_string())
         .with_dimension("function", lambda_function_name)
         .with_lambda_request_id("RequestId")
         .lambda_cold_start_metric("ColdStart")
@@ -73,23 +103,19 @@ pub fn initialize_metrics() -> &'static Collector {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
-    use crate::global::retention;
-
-    use super::{cloudwatch_logs, initialize_logger, initialize_metrics, log_group_tags};
+    use super::{cloudwatch_logs, cloudwatch_logs_for, cloudwatch_logs_for_profile, initialize_logger, initialize_metrics, s3, ssm};
 
     #[test]
     fn test_initialize_metrics() {
         std::env::set_var("AWS_LAMBDA_FUNCTION_NAME", "test-function-name");
-        initialize_metrics();
+        initialize_metrics("LogRotation");
     }
 
     #[test]
     #[should_panic]
     fn test_initialize_metrics_outside_lambda() {
         std::env::remove_var("AWS_LAMBDA_FUNCTION_NAME");
-        initialize_metrics();
+        initialize_metrics("LogRotation");
     }
 
     #[tokio::test]
@@ -98,54 +124,10 @@ mod tests {
         std::env::set_var("AWS_SECRET_ACCESS_KEY", "ASIAAFQWEFWEIFJ");
 
         cloudwatch_logs().await;
-    }
-
-    #[test]
-    fn test_retention() {
-        std::env::set_var("log_retention_in_days", "1");
-        assert_eq!(1, retention());
-    }
-
-    #[test]
-    fn test_retention_invalid() {
-        std::env::set_var("log_retention_in_days", "asdasdasd");
-        assert_eq!(30, retention());
-    }
-
-    #[test]
-    fn test_retention_not_set() {
-        std::env::remove_var("log_retention_in_days");
-        assert_eq!(30, retention());
-    }
-
-    #[test]
-    fn test_log_group_tags_none() {
-        std::env::remove_var("log_group_tags");
-        assert_eq!(log_group_tags(), None);
-    }
-
-    #[test]
-    fn test_log_group_tags_valid() {
-        let tags_json = "{\"a\": \"b\", \"c\": \"d\"}";
-        std::env::set_var("log_group_tags", tags_json);
-
-        let mut expected = HashMap::new();
-        expected.insert("a".to_string(), "b".to_string());
-        expected.insert("c".to_string(), "d".to_string());
-
-        assert_eq!(log_group_tags(), Some(expected));
-    }
-
-    #[test]
-    fn test_log_group_tags_invalid_none() {
-        std::env::set_var("log_group_tags", "true");
-        assert_eq!(log_group_tags(), None);
-    }
-
-    #[test]
-    fn test_log_group_tags_empty() {
-        std::env::set_var("log_group_tags", "{}");
-        assert_eq!(log_group_tags(), Some(HashMap::new()));
+        cloudwatch_logs_for(Some("eu-west-1".to_string()), None).await;
+        cloudwatch_logs_for_profile(None, Some("eu-west-1".to_string())).await;
+        s3().await;
+        ssm().await;
     }
 
     #[test]
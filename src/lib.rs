@@ -1,6 +1,24 @@
 pub mod cloudwatch_logs_traits;
+pub mod config;
+pub mod cross_account;
+pub mod dry_run;
 pub mod error;
 pub mod event;
+pub mod exclusions;
 pub mod global;
 pub mod metric_publisher;
+pub mod policy_parameter;
+pub mod rate_limiter;
+pub mod report;
+pub mod retention_days;
+pub mod retention_decision;
+pub mod retention_limits;
+pub mod retention_rules;
 pub mod retention_setter;
+pub mod retention_tag;
+pub mod rollback;
+pub mod s3_traits;
+pub mod ssm_traits;
+pub mod sts_traits;
+pub mod sweep;
+pub mod tagging;
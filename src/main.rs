@@ -1,3 +1,6 @@
+use std::time::SystemTime;
+
+use futures::{future, stream, StreamExt};
 use lambda_runtime::{Context, Error as LambdaRuntimeError, LambdaEvent};
 
 use log::{debug, error, info, trace, warn};
@@ -5,24 +8,46 @@ use serde_json::{json, Value as JsonValue};
 use terraform_aws_default_log_retention::global::initialize_metrics;
 use terraform_aws_default_log_retention::{
     cloudwatch_logs_traits::{DescribeLogGroups, ListTagsForResource, PutRetentionPolicy, TagResource},
+    config::{self, Config, ConfigSource},
+    dry_run::{is_dry_run, PlannedAction},
     error::{Error, Severity},
-    event::CloudTrailEvent,
-    global::{aws_partition, cloudwatch_logs, initialize_logger, log_group_tags, retention},
-    metric_publisher::{self, Metric, MetricName},
-    retention_setter::get_existing_retention,
+    event::{InvocationEvent, LogGroupRequest, SqsEvent, Trigger},
+    global::{cloudwatch_logs, initialize_logger, ssm},
+    metric_publisher::{self, Metric},
+    policy_parameter::PolicyStore,
+    retention_decision::RetentionDecision,
+    retention_limits::LimitAdjustment,
+    retention_setter::decide_and_apply,
+    ssm_traits::Ssm,
+    sweep::SweepTarget,
 };
 
-// TODO: Main and func are identical for main.rs and global_retention_setter.rs. How to genericize?
 #[tokio::main]
 // Ignore for code coverage
 #[cfg(not(tarpaulin_include))]
 async fn main() -> Result<(), LambdaRuntimeError> {
     initialize_logger();
 
-    let metrics = initialize_metrics();
+    // Fail at cold start rather than on every invocation if the configuration is invalid
+    let policy_store = match ConfigSource::load() {
+        Ok(source) => PolicyStore::load(source, ssm().await).await,
+        Err(error) => Err(vec![error]),
+    };
+    let policy_store = match policy_store {
+        Ok(policy_store) => policy_store,
+        Err(errors) => {
+            for error in &errors {
+                error!("Invalid configuration: {}", error);
+            }
+            return Err(config::combine(errors).into());
+        }
+    };
+
+    let metrics = initialize_metrics(&policy_store.config().await?.metric_namespace);
 
     trace!("Getting runtime result...");
-    let result = metrics_cloudwatch_embedded::lambda::handler::run(metrics, func).await;
+    let policy_store = &policy_store;
+    let result = metrics_cloudwatch_embedded::lambda::handler::run(metrics, |event| handler(event, policy_store)).await;
 
     match result {
         Ok(message) => {
@@ -36,25 +61,106 @@ async fn main() -> Result<(), LambdaRuntimeError> {
     }
 }
 
+/// Handles the event with the latest configuration
+// Ignore for code coverage
+#[cfg(not(tarpaulin_include))]
+async fn handler(event: LambdaEvent<JsonValue>, policy_store: &PolicyStore<Ssm>) -> Result<JsonValue, LambdaRuntimeError> {
+    match policy_store.config().await {
+        Ok(config) => func(event, &config).await,
+        Err(error) => process_error(error),
+    }
+}
+
 // Ignore for code coverage
 #[cfg(not(tarpaulin_include))]
-async fn func(event: LambdaEvent<JsonValue>) -> Result<JsonValue, LambdaRuntimeError> {
+async fn func(event: LambdaEvent<JsonValue>, config: &Config) -> Result<JsonValue, LambdaRuntimeError> {
     debug!("Received payload: {}. Context: {:?}", event.payload, event.context);
     let cloudwatch_logs = cloudwatch_logs().await;
-    let cloud_trail_event = parse_event(event.payload, Some(event.context));
-    if let Err(error) = cloud_trail_event {
-        return process_error(error);
+    let dry_run = is_dry_run(&event.payload, config.dry_run);
+    let changed_at = SystemTime::now();
+    let home = SweepTarget::from_function_arn(&event.context.invoked_function_arn);
+    let invocation_event = match parse_event(event.payload, Some(event.context)) {
+        Ok(invocation_event) => invocation_event,
+        Err(error) => return process_error(error),
+    };
+
+    if let InvocationEvent::Sqs(sqs_event) = invocation_event {
+        return Ok(process_sqs_batch(sqs_event, &home, &cloudwatch_logs, config, dry_run, changed_at).await);
     }
-    let cloud_trail_event = cloud_trail_event.expect("Should be Ok() based on above code.");
 
-    let result = process_event(cloud_trail_event, cloudwatch_logs).await;
+    let requests = match invocation_event.into_requests(&home) {
+        Ok(requests) => requests,
+        Err(error) => return process_error(error),
+    };
 
-    match result {
-        Ok(message) => Ok(message),
-        Err(error) => process_error(error),
+    // SNS can deliver several events at once
+    let mut responses = vec![];
+    for request in requests {
+        let response = match process_event(request, &cloudwatch_logs, config, dry_run, changed_at).await {
+            Ok(message) => message,
+            Err(error) => process_error(error)?,
+        };
+        responses.push(response);
+    }
+
+    match responses.len() {
+        1 => Ok(responses.remove(0)),
+        _ => Ok(json!(responses)),
     }
 }
 
+/// Processes the records of an SQS batch concurrently. Records which hit an error are returned in `batchItemFailures`
+/// so only they are retried; records which only hit warnings, e.g. the log group was already deleted, are acknowledged.
+async fn process_sqs_batch(
+    sqs_event: SqsEvent,
+    home: &SweepTarget,
+    cloudwatch_logs: &(impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource),
+    config: &Config,
+    dry_run: bool,
+    changed_at: SystemTime,
+) -> JsonValue {
+    let batch_item_failures: Vec<JsonValue> = stream::iter(sqs_event.records)
+        .map(|record| async move {
+            match process_sqs_record(&record.body, home, cloudwatch_logs, config, dry_run, changed_at).await {
+                Ok(()) => None,
+                Err(error) if error.severity == Severity::Warning => {
+                    warn!("WARN processing SQS message {}: {}", record.message_id, error);
+                    None
+                }
+                Err(error) => {
+                    error!("ERROR processing SQS message {}: {}", record.message_id, error);
+                    Some(json!({ "itemIdentifier": record.message_id }))
+                }
+            }
+        })
+        .buffer_unordered(config.policy.concurrency)
+        .filter_map(future::ready)
+        .collect()
+        .await;
+
+    json!({ "batchItemFailures": batch_item_failures })
+}
+
+/// Returns the first error, or the last warning if there were no errors
+async fn process_sqs_record(
+    body: &str,
+    home: &SweepTarget,
+    cloudwatch_logs: &(impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource),
+    config: &Config,
+    dry_run: bool,
+    changed_at: SystemTime,
+) -> Result<(), Error> {
+    let mut result = Ok(());
+    for request in InvocationEvent::from_message(body)?.into_requests(home)? {
+        match process_event(request, cloudwatch_logs, config, dry_run, changed_at).await {
+            Ok(_) => {}
+            Err(error) if error.severity == Severity::Error => return Err(error),
+            Err(warning) => result = Err(warning),
+        }
+    }
+    result
+}
+
 /// Returns Ok if error is just a warning
 fn process_error(error: Error) -> Result<JsonValue, LambdaRuntimeError> {
     match error.severity {
@@ -70,74 +176,87 @@ fn process_error(error: Error) -> Result<JsonValue, LambdaRuntimeError> {
 }
 
 async fn process_event(
-    event: CloudTrailEvent,
-    cloudwatch_logs: impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource,
+    request: LogGroupRequest,
+    cloudwatch_logs: &(impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource),
+    config: &Config,
+    dry_run: bool,
+    changed_at: SystemTime,
 ) -> Result<JsonValue, Error> {
-    let log_group_name = event.detail.request_parameters.log_group_name;
-
-    let existing_retention = get_existing_retention(&log_group_name, &cloudwatch_logs).await?;
-
-    if existing_retention != 0 {
-        info!(
-            "Not setting retention for {} because it is set to {} days already.",
-            log_group_name, existing_retention
-        );
-        metric_publisher::publish_metric(Metric::new(MetricName::AlreadyHasRetention, 1));
-        return Ok(json!({
-            "message":
-                format!(
-                    "Not setting retention for {} because it is set to {} days already.",
-                    log_group_name, existing_retention
-                )
-        }));
-    }
-
-    let log_group_arn = format!(
-        "arn:{}:logs:{}:{}:log-group:{}",
-        aws_partition(),
-        event.detail.aws_region,
-        event.detail.user_identity.account_id,
-        log_group_name
-    );
-    let tags = cloudwatch_logs.list_tags_for_resource(&log_group_arn).await?;
-    if let Some(retention) = tags.tags().and_then(|tags| tags.get("retention")) {
-        info!(
-            "Not setting retention for {} because tag `retention`=`{}` exists on it.",
-            log_group_name, retention
-        );
-        metric_publisher::publish_metric(Metric::new(MetricName::AlreadyTaggedWithRetention, 1));
-        return Ok(json!({
-            "message":
-                format!(
-                    "Not setting retention for {} because tag `retention`=`{}` exists on it.",
-                    log_group_name, retention
-                )
-        }));
+    let policy = &config.policy;
+    let log_group_arn = request.log_group_arn(&config.aws_partition);
+    let log_group_name = request.log_group_name;
+    let trigger = request.trigger;
+    match trigger {
+        Trigger::CreateLogGroup => {}
+        Trigger::PutRetentionPolicy => info!("Retention of {} was changed; re-evaluating it.", log_group_name),
+        Trigger::DeleteRetentionPolicy => info!("Retention of {} was removed; re-applying the default.", log_group_name),
+        Trigger::TagResource | Trigger::UntagResource => info!("Tags of {} were changed; re-evaluating it.", log_group_name),
     }
 
-    cloudwatch_logs.put_retention_policy(&log_group_name, retention()).await?;
-
-    metric_publisher::publish_metric(Metric::new(MetricName::Updated, 1));
+    let decision = decide_and_apply(&log_group_name, &log_group_arn, None, cloudwatch_logs, policy, dry_run, changed_at).await?;
+    // Every record of an SQS or SNS batch is processed in the same invocation, so each adds to the count rather than replacing it
+    metric_publisher::increment_metric(Metric::new(decision.metric_name(), 1).with_dry_run(dry_run).with_trigger(trigger));
+
+    let message = match &decision {
+        RetentionDecision::SetRetention { rule_match, .. } if !dry_run => {
+            info!(
+                "Retention of {} days set successfully for {} (rule `{}`)",
+                rule_match.retention_in_days, log_group_name, rule_match.rule
+            );
+            return Ok(json!({"message": "Retention set successfully", "rule": rule_match.rule, "retentionInDays": rule_match.retention_in_days}));
+        }
+        RetentionDecision::EnforceLimit {
+            previous_retention_in_days,
+            adjustment,
+            ..
+        } if !dry_run => {
+            let message = match adjustment {
+                LimitAdjustment::ReducedToMaximum(_) => "Retention reduced to maximum",
+                LimitAdjustment::RaisedToMinimum(_) => "Retention raised to minimum",
+            };
+            let retention_in_days = adjustment.retention_in_days();
+            info!(
+                "{} for {}: {} -> {} days",
+                message, log_group_name, previous_retention_in_days, retention_in_days
+            );
+            return Ok(json!({"message": message, "previousRetentionInDays": previous_retention_in_days, "retentionInDays": retention_in_days}));
+        }
+        RetentionDecision::SetRetention { .. } | RetentionDecision::EnforceLimit { .. } => "Dry run; no changes made".to_string(),
+        skip => {
+            let message = format!(
+                "Not setting retention for {} because {}.",
+                log_group_name,
+                skip.skip_reason().unwrap_or_default()
+            );
+            info!("{}", message);
+            message
+        }
+    };
+    Ok(response(message, decision.plan(&log_group_name, &policy.tagging, changed_at), dry_run))
+}
 
-    if let Some(tags) = log_group_tags() {
-        cloudwatch_logs.tag_resource(&log_group_arn, tags).await?;
+/// In dry-run mode, the plan is logged and returned alongside the message
+fn response(message: String, plan: PlannedAction, dry_run: bool) -> JsonValue {
+    match dry_run {
+        true => {
+            info!("[DRY RUN] {}", plan);
+            json!({"message": message, "dryRun": true, "plan": plan})
+        }
+        false => json!({ "message": message }),
     }
-
-    info!("Retention set successfully for {}", log_group_name);
-    Ok(json!({"message": "Retention set successfully"}))
 }
 
-/// Parses a JsonValue into a CloudTrailEvent
+/// Parses a JsonValue into an InvocationEvent
 /// Normally we could allow our func to parse the event for us, but it doesn't handle errors gracefully or with enough information.
 ///
 /// # Arguments
 ///    
 /// * `payload` the original payload given by Lambda runtime
 /// * `context` Optionally, provide the Context object given by the Lambda runtime. It isn't needed for execution; only to enhance the returned error if the payload fails to parse
-fn parse_event(payload: JsonValue, context: Option<Context>) -> Result<CloudTrailEvent, Error> {
+fn parse_event(payload: JsonValue, context: Option<Context>) -> Result<InvocationEvent, Error> {
     // Must clone payload so we can optionally use it in the error message
-    let cloud_trail_event = serde_json::from_value(payload.clone());
-    if let Err(error) = cloud_trail_event {
+    let invocation_event = serde_json::from_value(payload.clone());
+    if let Err(error) = invocation_event {
         // Known instances are:
         // * When someone tried to make a group but they don't have access
         return Err(Error {
@@ -148,14 +267,17 @@ fn parse_event(payload: JsonValue, context: Option<Context>) -> Result<CloudTrai
             ),
         });
     }
-    let cloud_trail_event = cloud_trail_event.expect("Cannot be Err based on code above");
+    let invocation_event = invocation_event.expect("Cannot be Err based on code above");
 
-    Ok(cloud_trail_event)
+    Ok(invocation_event)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{
+        collections::HashMap,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
 
     use async_trait::async_trait;
     use aws_sdk_cloudwatchlogs::{
@@ -169,22 +291,23 @@ mod tests {
     use lambda_runtime::{Context, LambdaEvent};
     use mockall::{mock, predicate};
     use serde_json::json;
-    use terraform_aws_default_log_retention::event::CloudTrailEvent;
+    use terraform_aws_default_log_retention::config::{Config, ConfigSource};
+    use terraform_aws_default_log_retention::event::{CloudTrailEvent, InvocationEvent, LogGroupRequest, SqsEvent, Trigger};
+    use terraform_aws_default_log_retention::exclusions::Exclusions;
+    use terraform_aws_default_log_retention::retention_decision::DecisionPolicy;
+    use terraform_aws_default_log_retention::sweep::SweepTarget;
     use terraform_aws_default_log_retention::{
         cloudwatch_logs_traits::{DescribeLogGroups, ListTagsForResource, PutRetentionPolicy, TagResource},
         error::{Error, Severity},
+        retention_days::RetentionDays,
+        retention_limits::{MaximumRetention, MinimumRetention, RetentionLimits},
     };
 
-    use crate::{func, parse_event, process_error, process_event};
-
-    #[ctor::ctor]
-    fn init() {
-        std::env::set_var("log_group_tags", "{}");
-    }
+    use crate::{func, parse_event, process_error, process_event, process_sqs_batch};
 
     #[tokio::test]
     async fn test_process_event_success_no_tags() {
-        let event = CloudTrailEvent::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
         let log_group_arn = "arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated";
 
         let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
@@ -208,11 +331,13 @@ mod tests {
 
         mock_cloud_watch_logs_client
             .expect_tag_resource()
-            .with(predicate::eq(log_group_arn), predicate::eq(HashMap::new()))
+            .with(predicate::eq(log_group_arn), predicate::eq(marked("never-expire")))
             .once()
             .returning(|_, _| Ok(TagResourceOutput::builder().build()));
 
-        let result = process_event(event, mock_cloud_watch_logs_client).await.expect("Should not fail");
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at())
+            .await
+            .expect("Should not fail");
 
         insta::assert_debug_snapshot!(result);
     }
@@ -220,8 +345,7 @@ mod tests {
     #[tokio::test]
     // Testing for govcloud or China
     async fn test_process_event_success_no_tags_different_aws_partition() {
-        std::env::set_var("aws_partition", "aws-cn");
-        let event = CloudTrailEvent::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
         let log_group_arn = "arn:aws-cn:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated";
 
         let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
@@ -245,19 +369,24 @@ mod tests {
 
         mock_cloud_watch_logs_client
             .expect_tag_resource()
-            .with(predicate::eq(log_group_arn), predicate::eq(HashMap::new()))
+            .with(predicate::eq(log_group_arn), predicate::eq(marked("never-expire")))
             .once()
             .returning(|_, _| Ok(TagResourceOutput::builder().build()));
 
-        let result = process_event(event, mock_cloud_watch_logs_client).await.expect("Should not fail");
+        let config = Config {
+            aws_partition: "aws-cn".to_string(),
+            ..config(policy())
+        };
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config, false, changed_at())
+            .await
+            .expect("Should not fail");
 
-        std::env::remove_var("aws_partition");
         insta::assert_debug_snapshot!(result);
     }
 
     #[tokio::test]
     async fn test_process_event_fails_when_put_retention_policy_fails() {
-        let event = CloudTrailEvent::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
 
         let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
         mock_cloud_watch_logs_client
@@ -282,14 +411,16 @@ mod tests {
                 ))
             });
 
-        let error = process_event(event, mock_cloud_watch_logs_client).await.expect_err("Should fail");
+        let error = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at())
+            .await
+            .expect_err("Should fail");
 
         insta::assert_debug_snapshot!(error);
     }
 
     #[tokio::test]
     async fn test_process_event_fails_when_tag_log_group_fails() {
-        let event = CloudTrailEvent::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
 
         let log_group_arn = "arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated";
 
@@ -314,7 +445,7 @@ mod tests {
 
         mock_cloud_watch_logs_client
             .expect_tag_resource()
-            .with(predicate::eq(log_group_arn), predicate::eq(HashMap::new()))
+            .with(predicate::eq(log_group_arn), predicate::eq(marked("never-expire")))
             .once()
             .returning(|_, _| {
                 // This type of error would never happen because it is "my" error type rather than an AWS error type. Luckily it doesn't matter -- we only care that an error happened.
@@ -323,14 +454,16 @@ mod tests {
                 ))
             });
 
-        let error = process_event(event, mock_cloud_watch_logs_client).await.expect_err("Should fail");
+        let error = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at())
+            .await
+            .expect_err("Should fail");
 
         insta::assert_debug_snapshot!(error);
     }
 
     #[tokio::test]
     async fn test_process_event_retention_already_set() {
-        let event = CloudTrailEvent::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
 
         let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
         mock_cloud_watch_logs_client
@@ -339,14 +472,62 @@ mod tests {
             .once()
             .returning(|_, _| mock_describe_log_groups_response("MyLogGroupWasCreated", 30));
 
-        let result = process_event(event, mock_cloud_watch_logs_client).await.expect("Should not fail");
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq("arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated"))
+            .once()
+            .returning(|_| mock_list_tags_for_resource_response(None));
+
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_debug_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_event_retention_tag_overrides_rules() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("MyLogGroupWasCreated".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("MyLogGroupWasCreated", 30));
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq("arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated"))
+            .once()
+            .returning(|_| mock_list_tags_for_resource_response(Some("14")));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(14))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        // Retention from the retention tag is not marked, but the change is recorded
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq("arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated"),
+                predicate::eq(change_record("30")),
+            )
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at())
+            .await
+            .expect("Should not fail");
 
         insta::assert_debug_snapshot!(result);
     }
 
     #[tokio::test]
     async fn test_process_event_do_not_overwrite_when_retention_tag_set() {
-        let event = CloudTrailEvent::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
 
         let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
         mock_cloud_watch_logs_client
@@ -361,14 +542,200 @@ mod tests {
             .once()
             .returning(|_| mock_list_tags_for_resource_response(Some("Do not override please")));
 
-        let result = process_event(event, mock_cloud_watch_logs_client).await.expect("Should not fail");
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_debug_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_event_dry_run() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("MyLogGroupWasCreated".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("MyLogGroupWasCreated", 0));
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq("arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated"))
+            .once()
+            .returning(|_| mock_list_tags_for_resource_response(None));
+
+        mock_cloud_watch_logs_client.expect_put_retention_policy().never();
+        mock_cloud_watch_logs_client.expect_tag_resource().never();
+
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), true, changed_at())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_debug_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_event_dry_run_retention_already_set() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("MyLogGroupWasCreated".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("MyLogGroupWasCreated", 30));
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq("arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated"))
+            .once()
+            .returning(|_| mock_list_tags_for_resource_response(None));
+
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), true, changed_at())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_debug_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_event_reduces_retention_above_maximum() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+        let log_group_arn = "arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated";
+
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("MyLogGroupWasCreated".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("MyLogGroupWasCreated", 3653));
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq(log_group_arn))
+            .once()
+            .returning(|_| mock_list_tags_for_resource_response(None));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(365))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        let expected_tags = change_record("3653");
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::eq(log_group_arn), predicate::eq(expected_tags))
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let retention_limits = RetentionLimits {
+            maximum: Some(MaximumRetention::new(RetentionDays::try_from(365).unwrap(), vec![])),
+            minimum: None,
+        };
+        let result = process_event(
+            event,
+            &mock_cloud_watch_logs_client,
+            &config(DecisionPolicy { retention_limits, ..policy() }),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
+
+        insta::assert_debug_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_event_raises_retention_below_minimum() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+        let log_group_arn = "arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated";
+
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("MyLogGroupWasCreated".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("MyLogGroupWasCreated", 1));
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq(log_group_arn))
+            .once()
+            .returning(|_| mock_list_tags_for_resource_response(None));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(90))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        let expected_tags = change_record("1");
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::eq(log_group_arn), predicate::eq(expected_tags))
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let retention_limits = RetentionLimits {
+            maximum: None,
+            minimum: Some(MinimumRetention::new(RetentionDays::try_from(90).unwrap(), vec![])),
+        };
+        let result = process_event(
+            event,
+            &mock_cloud_watch_logs_client,
+            &config(DecisionPolicy { retention_limits, ..policy() }),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
+
+        insta::assert_debug_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_event_minimum_respects_opt_out() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroupWasCreated");
+
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("MyLogGroupWasCreated".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("MyLogGroupWasCreated", 1));
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq("arn:aws:logs:us-east-1:123456789:log-group:MyLogGroupWasCreated"))
+            .once()
+            .returning(|_| mock_list_tags_for_resource_response(Some("short-lived on purpose")));
+
+        mock_cloud_watch_logs_client.expect_put_retention_policy().never();
+        mock_cloud_watch_logs_client.expect_tag_resource().never();
+
+        let retention_limits = RetentionLimits {
+            maximum: None,
+            minimum: Some(MinimumRetention::new(RetentionDays::try_from(90).unwrap(), vec![])),
+        };
+        let result = process_event(
+            event,
+            &mock_cloud_watch_logs_client,
+            &config(DecisionPolicy { retention_limits, ..policy() }),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
 
         insta::assert_debug_snapshot!(result);
     }
 
     #[test]
     fn test_parse_event_success() {
-        let expected = CloudTrailEvent::new("123", "us-east-77", "SomeLogGroup");
+        let expected = InvocationEvent::EventBridge(CloudTrailEvent::new("123", "us-east-77", "SomeLogGroup"));
         let input = json!(expected);
 
         assert_eq!(expected, parse_event(input, None).expect("Should succeed"));
@@ -404,11 +771,196 @@ mod tests {
         .expect("Should be successful");
     }
 
+    #[tokio::test]
+    async fn test_process_event_reapplies_default_when_retention_removed() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroup").with_trigger(Trigger::DeleteRetentionPolicy);
+
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("MyLogGroup".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("MyLogGroup", 0));
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| mock_list_tags_for_resource_response(None));
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroup"), predicate::eq(30))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(
+            json!({"message": "Retention set successfully", "rule": "default", "retentionInDays": 30}),
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_event_reapplies_default_when_retention_removed_from_marked_group() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroup").with_trigger(Trigger::DeleteRetentionPolicy);
+        let log_group_arn = "arn:aws:logs:us-east-1:123456789:log-group:MyLogGroup";
+
+        // The group was set to 30 days by this module, then someone deleted its retention policy
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("MyLogGroup", 0));
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().set_tags(Some(marked("never-expire"))).build()));
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroup"), predicate::eq(30))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::eq(log_group_arn), predicate::eq(marked("never-expire")))
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let result = process_event(event, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(
+            json!({"message": "Retention set successfully", "rule": "default", "retentionInDays": 30}),
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_event_leaves_rolled_back_group() {
+        let retention_limits = RetentionLimits {
+            maximum: Some(MaximumRetention::new(RetentionDays::try_from(365).unwrap(), vec![])),
+            minimum: None,
+        };
+        let config = config(DecisionPolicy { retention_limits, ..policy() });
+
+        // A rollback tags the group, then deletes its retention policy or puts back a retention above the maximum.
+        // The owner's retention tag is left in place, but does not apply while the group is tagged as rolled back.
+        let rolled_back_to_never_expire =
+            "Not setting retention for MyLogGroup because tag `retention-rolled-back-at`=`2023-11-14T22:13:20Z` exists on it.".to_string();
+        let rolled_back_above_maximum = "Not setting retention for MyLogGroup because it has retention of 3653 days already.".to_string();
+        for (trigger, retention_in_days, message) in [
+            (Trigger::DeleteRetentionPolicy, 0, rolled_back_to_never_expire),
+            (Trigger::PutRetentionPolicy, 3653, rolled_back_above_maximum),
+        ] {
+            let event = LogGroupRequest::new("123456789", "us-east-1", "MyLogGroup").with_trigger(trigger);
+
+            let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+            mock_cloud_watch_logs_client
+                .expect_describe_log_groups()
+                .once()
+                .returning(move |_, _| mock_describe_log_groups_response("MyLogGroup", retention_in_days));
+            mock_cloud_watch_logs_client.expect_list_tags_for_resource().once().returning(|_| {
+                let tags = HashMap::from([
+                    ("retention".to_string(), "14".to_string()),
+                    ("retention-rolled-back-at".to_string(), "2023-11-14T22:13:20Z".to_string()),
+                ]);
+                Ok(ListTagsForResourceOutput::builder().set_tags(Some(tags)).build())
+            });
+            mock_cloud_watch_logs_client.expect_put_retention_policy().never();
+            mock_cloud_watch_logs_client.expect_tag_resource().never();
+
+            let result = process_event(event, &mock_cloud_watch_logs_client, &config, false, changed_at())
+                .await
+                .expect("Should not fail");
+
+            assert_eq!(json!({"message": message}), result);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_event_excluded() {
+        let event = LogGroupRequest::new("123456789", "us-east-1", "/aws/vendedlogs/states/abc");
+        let exclusions = Exclusions::parse(r#"{"prefixes": ["/aws/vendedlogs/"]}"#).unwrap();
+
+        // No API calls are made for excluded log groups
+        let mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+
+        let result = process_event(
+            event,
+            &mock_cloud_watch_logs_client,
+            &config(DecisionPolicy { exclusions, ..policy() }),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(
+            json!({"message": "Not setting retention for /aws/vendedlogs/states/abc because it is excluded by prefix `/aws/vendedlogs/`."}),
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_sqs_batch_only_fails_records_with_errors() {
+        let event: SqsEvent = serde_json::from_value(json!({"Records": [
+            {"messageId": "has-retention", "body": json!(CloudTrailEvent::new("123", "us-east-1", "HasRetention")).to_string()},
+            {"messageId": "unparseable", "body": "not an event"},
+            {"messageId": "deleted", "body": json!({"logGroupName": "Deleted"}).to_string()},
+            {"messageId": "throttled", "body": json!(CloudTrailEvent::new("123", "us-east-1", "Throttled")).to_string()},
+        ]}))
+        .unwrap();
+        let home = SweepTarget {
+            account_id: Some("123".to_string()),
+            region: Some("us-east-1".to_string()),
+        };
+
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("HasRetention".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("HasRetention", 30));
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("Deleted".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| Ok(DescribeLogGroupsOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("Throttled".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| mock_describe_log_groups_response("Throttled", 0));
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .times(2)
+            .returning(|_| mock_list_tags_for_resource_response(None));
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("Throttled"), predicate::eq(30))
+            .once()
+            .returning(|_, _| {
+                Err(CloudWatchLogsError::DataAlreadyAcceptedException(
+                    DataAlreadyAcceptedException::builder().build(),
+                ))
+            });
+
+        let result = process_sqs_batch(event, &home, &mock_cloud_watch_logs_client, &config(policy()), false, changed_at()).await;
+
+        assert_eq!(json!({"batchItemFailures": [{"itemIdentifier": "throttled"}]}), result);
+    }
+
     #[tokio::test]
     async fn test_process_event_bad_input() {
         let input = json!({"invalid": "input"});
         let event = LambdaEvent::new(input, Context::default());
-        let result = func(event).await.expect("Should be OK with error message (warning).");
+        let result = func(event, &config(policy())).await.expect("Should be OK with error message (warning).");
 
         insta::assert_debug_snapshot!(result);
     }
@@ -456,14 +1008,48 @@ mod tests {
         }
     }
 
-    #[allow(clippy::result_large_err)] // This is a test, don't care about large err type
+    /// The default configuration, with `log_group_tags` set so that groups are tagged
+    fn default_config() -> Config {
+        let source = ConfigSource {
+            log_group_tags: Some("{}".to_string()),
+            ..Default::default()
+        };
+        Config::from_source(&source).unwrap()
+    }
+
+    fn config(policy: DecisionPolicy) -> Config {
+        Config { policy, ..default_config() }
+    }
+
+    fn policy() -> DecisionPolicy {
+        default_config().policy
+    }
+
+    fn changed_at() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    /// Tags recording a change from `previous_retention` at `changed_at()`
+    fn change_record(previous_retention: &str) -> HashMap<String, String> {
+        HashMap::from([
+            ("previous-retention".to_string(), previous_retention.to_string()),
+            ("retention-changed-at".to_string(), "2023-11-14T22:13:20Z".to_string()),
+        ])
+    }
+
+    /// `change_record` plus the default marker, i.e. every tag sent with the default `TaggingPolicy` and empty `log_group_tags`
+    fn marked(previous_retention: &str) -> HashMap<String, String> {
+        let mut tags = change_record(previous_retention);
+        tags.insert("retention".to_string(), "Set by AWS Default Log Retention project.".to_string());
+        tags
+    }
+
     fn mock_describe_log_groups_response(log_group_name: &str, retention: i32) -> Result<DescribeLogGroupsOutput, CloudWatchLogsError> {
         let log_group = LogGroup::builder().log_group_name(log_group_name).retention_in_days(retention).build();
         let response = DescribeLogGroupsOutput::builder().log_groups(log_group).build();
         Ok(response)
     }
 
-    #[allow(clippy::result_large_err)] // This is a test, don't care about large err type
     fn mock_list_tags_for_resource_response(retention_tag_value: Option<&str>) -> Result<ListTagsForResourceOutput, CloudWatchLogsError> {
         if let Some(retention_tag_value) = retention_tag_value {
             let mut tags: HashMap<String, String> = HashMap::new();
@@ -1,3 +1,5 @@
+use crate::event::Trigger;
+
 // Enumerates the titles of metric names
 // to ensure consistency between Lambdas
 #[derive(Debug, Clone)]
@@ -6,18 +8,49 @@ pub enum MetricName {
     Updated,
     AlreadyHasRetention,
     AlreadyTaggedWithRetention,
+    ReducedToMaximum,
+    RaisedToMinimum,
     Errored,
+    Excluded,
+    FailedTargets,
 }
 
 #[derive(Debug, Clone)]
 pub struct Metric {
     pub name: MetricName,
     pub value: u64,
+    /// Extra dimensions on top of the `function` dimension added in `global::initialize_metrics`
+    pub dimensions: Vec<(String, String)>,
 }
 
 impl Metric {
     pub fn new(name: MetricName, value: u64) -> Self {
-        Self { name, value }
+        Self {
+            name,
+            value,
+            dimensions: vec![],
+        }
+    }
+
+    pub fn with_dimension(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.dimensions.push((key.into(), value.into()));
+        self
+    }
+
+    /// Dry-run metrics are emitted under a `Mode`=`DryRun` dimension so they do not mix with real changes
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        match dry_run {
+            true => self.with_dimension("Mode", "DryRun"),
+            false => self,
+        }
+    }
+
+    /// Metrics from events other than log group creation carry a `Trigger` dimension naming the API call, e.g. `DeleteRetentionPolicy`
+    pub fn with_trigger(self, trigger: Trigger) -> Self {
+        match trigger {
+            Trigger::CreateLogGroup => self,
+            trigger => self.with_dimension("Trigger", trigger.to_string()),
+        }
     }
 }
 
@@ -33,8 +66,16 @@ pub fn publish_metrics(metrics: Vec<Metric>) {
     }
 }
 
+/// Sets the metric to `metric.value` for this invocation, for totals which are counted before they are published, e.g. by a sweep
 pub fn publish_metric(metric: Metric) {
-    metrics::absolute_counter!(metric.name.to_string(), metric.value);
+    let labels: Vec<metrics::Label> = metric.dimensions.into_iter().map(|(key, value)| metrics::Label::new(key, value)).collect();
+    metrics::absolute_counter!(metric.name.to_string(), metric.value, labels);
+}
+
+/// Adds `metric.value` to the metric for this invocation, for metrics published once per event, e.g. for each record of an SQS batch
+pub fn increment_metric(metric: Metric) {
+    let labels: Vec<metrics::Label> = metric.dimensions.into_iter().map(|(key, value)| metrics::Label::new(key, value)).collect();
+    metrics::counter!(metric.name.to_string(), metric.value, labels);
 }
 
 #[cfg(test)]
@@ -43,24 +84,38 @@ mod tests {
 
     #[test]
     fn test_publish_metric() {
-        publish_metric(Metric {
-            name: MetricName::AlreadyHasRetention,
-            value: 1237,
-        })
+        publish_metric(Metric::new(MetricName::AlreadyHasRetention, 1237))
+    }
+
+    #[test]
+    fn test_increment_metric() {
+        increment_metric(Metric::new(MetricName::Updated, 1));
+        increment_metric(Metric::new(MetricName::Updated, 1));
     }
 
     #[test]
     fn test_publish_metrics() {
         let metrics = vec![
-            Metric {
-                name: MetricName::AlreadyHasRetention,
-                value: 1,
-            },
-            Metric {
-                name: MetricName::AlreadyTaggedWithRetention,
-                value: 2,
-            },
+            Metric::new(MetricName::AlreadyHasRetention, 1),
+            Metric::new(MetricName::AlreadyTaggedWithRetention, 2),
         ];
         publish_metrics(metrics);
     }
+
+    #[test]
+    fn test_with_dry_run() {
+        assert!(Metric::new(MetricName::Updated, 1).with_dry_run(false).dimensions.is_empty());
+
+        let metric = Metric::new(MetricName::Updated, 1).with_dry_run(true);
+        assert_eq!(vec![("Mode".to_string(), "DryRun".to_string())], metric.dimensions);
+        publish_metric(metric);
+    }
+
+    #[test]
+    fn test_with_trigger() {
+        assert!(Metric::new(MetricName::Updated, 1).with_trigger(Trigger::CreateLogGroup).dimensions.is_empty());
+
+        let metric = Metric::new(MetricName::Updated, 1).with_trigger(Trigger::DeleteRetentionPolicy);
+        assert_eq!(vec![("Trigger".to_string(), "DeleteRetentionPolicy".to_string())], metric.dimensions);
+    }
 }
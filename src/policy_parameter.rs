@@ -0,0 +1,239 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{error, info};
+use tokio::time::Instant;
+
+use crate::{
+    config::{self, Config, ConfigSource},
+    error::{Error, Severity},
+    ssm_traits::GetParameter,
+};
+
+/// Serves the `Config` loaded at cold start, with the settings in the `policy_parameter_name` SSM parameter layered on top if it is set,
+/// so that e.g. the default retention, tags or exclusions can be changed without a Terraform apply.
+/// The parameter is checked at most once every `policy_parameter_ttl` across warm invocations, and the configuration is only rebuilt when its version changes.
+pub struct PolicyStore<S> {
+    source: ConfigSource,
+    parameter: Option<PolicyParameter<S>>,
+    cached: Mutex<CachedConfig>,
+}
+
+struct PolicyParameter<S> {
+    ssm: S,
+    name: String,
+    ttl: Duration,
+}
+
+struct CachedConfig {
+    config: Arc<Config>,
+    /// Version of the parameter `config` was built from, or None before it has been read
+    version: Option<i64>,
+    checked_at: Option<Instant>,
+}
+
+impl<S: GetParameter> PolicyStore<S> {
+    /// Validates `source` and, if it names a policy parameter, the parameter's settings too. `ssm` is only used when it does.
+    pub async fn load(source: ConfigSource, ssm: S) -> Result<Self, Vec<Error>> {
+        let config = Config::from_source(&source)?;
+        let parameter = config.policy_parameter_name.clone().map(|name| PolicyParameter {
+            ssm,
+            name,
+            ttl: config.policy_parameter_ttl,
+        });
+        let store = Self {
+            source,
+            parameter,
+            cached: Mutex::new(CachedConfig {
+                config: Arc::new(config),
+                version: None,
+                checked_at: None,
+            }),
+        };
+
+        store.config().await.map_err(|error| vec![error])?;
+        Ok(store)
+    }
+
+    /// The latest configuration. Once the parameter has been read, a version which cannot be read or holds invalid settings
+    /// is logged and the last good configuration kept, rather than failing every invocation.
+    pub async fn config(&self) -> Result<Arc<Config>, Error> {
+        let parameter = match &self.parameter {
+            Some(parameter) => parameter,
+            None => return Ok(self.cached.lock().expect("Never poisoned").config.clone()),
+        };
+
+        let version = {
+            let cached = self.cached.lock().expect("Never poisoned");
+            if cached.checked_at.is_some_and(|checked_at| checked_at.elapsed() < parameter.ttl) {
+                return Ok(cached.config.clone());
+            }
+            cached.version
+        };
+        let reloaded = self.reload(parameter, version).await;
+
+        let mut cached = self.cached.lock().expect("Never poisoned");
+        cached.checked_at = Some(Instant::now());
+        match reloaded {
+            Ok(Some((version, config))) => {
+                cached.config = Arc::new(config);
+                cached.version = Some(version);
+            }
+            Ok(None) => {}
+            Err(error) if cached.version.is_none() => return Err(error),
+            Err(error) => error!("Keeping settings from version {:?} of {}: {}", cached.version, parameter.name, error),
+        }
+        Ok(cached.config.clone())
+    }
+
+    /// The configuration with the parameter's settings, or None if its version is still `version`
+    async fn reload(&self, parameter: &PolicyParameter<S>, version: Option<i64>) -> Result<Option<(i64, Config)>, Error> {
+        let output = parameter.ssm.get_parameter(&parameter.name).await.map_err(|e| Error {
+            message: format!("Could not read policy parameter {}: {}", parameter.name, e),
+            severity: Severity::Error,
+        })?;
+        let (latest, value) = match output.parameter() {
+            Some(latest) => (latest.version(), latest.value().unwrap_or_default()),
+            None => {
+                return Err(Error {
+                    message: format!("Policy parameter {} has no value", parameter.name),
+                    severity: Severity::Error,
+                })
+            }
+        };
+        if Some(latest) == version {
+            return Ok(None);
+        }
+
+        info!("Loading settings from version {} of {}", latest, parameter.name);
+        let settings = ConfigSource::from_json_str(value)?;
+        let config = Config::from_source(&self.source.overlaid_with(&settings)).map_err(config::combine)?;
+        Ok(Some((latest, config)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use aws_sdk_ssm::{
+        operation::get_parameter::GetParameterOutput,
+        types::{error::ParameterNotFound, Parameter},
+        Error as SsmError,
+    };
+    use mockall::{predicate, Sequence};
+
+    use crate::{
+        config::{Config, ConfigSource},
+        ssm_traits::MockGetParameter,
+    };
+
+    use super::PolicyStore;
+
+    fn source(policy_parameter_name: Option<&str>) -> ConfigSource {
+        ConfigSource {
+            log_group_tags: Some(r#"{"team": "platform"}"#.to_string()),
+            policy_parameter_name: policy_parameter_name.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn parameter(version: i64, value: &str) -> Result<GetParameterOutput, SsmError> {
+        let parameter = Parameter::builder().name("/log-retention/policy").version(version).value(value).build();
+        Ok(GetParameterOutput::builder().parameter(parameter).build())
+    }
+
+    fn default_retention(config: &Config) -> i32 {
+        config.policy.retention_rules.evaluate("/aws/lambda/abc").retention_in_days.days()
+    }
+
+    #[tokio::test]
+    async fn test_without_parameter() {
+        let mut mock_ssm = MockGetParameter::new();
+        mock_ssm.expect_get_parameter().never();
+
+        let store = PolicyStore::load(source(None), mock_ssm).await.unwrap();
+
+        assert_eq!(30, default_retention(&store.config().await.unwrap()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reloads_when_version_changes() {
+        let mut sequence = Sequence::new();
+        let mut mock_ssm = MockGetParameter::new();
+        for (version, value) in [
+            (1, r#"{"log_retention_in_days": 14}"#),
+            (1, r#"{"log_retention_in_days": 14}"#),
+            (
+                2,
+                r#"{"log_retention_in_days": 90, "log_group_exclusions": {"prefixes": ["/aws/vendedlogs/"]}}"#,
+            ),
+        ] {
+            mock_ssm
+                .expect_get_parameter()
+                .with(predicate::eq("/log-retention/policy"))
+                .once()
+                .in_sequence(&mut sequence)
+                .returning(move |_| parameter(version, value));
+        }
+
+        let store = PolicyStore::load(source(Some("/log-retention/policy")), mock_ssm).await.unwrap();
+        let first = store.config().await.unwrap();
+        assert_eq!(14, default_retention(&first));
+        // Settings not in the parameter are kept
+        assert!(first.policy.retention_rules.evaluate("/aws/lambda/abc").tags.unwrap().contains_key("team"));
+
+        // Cached until the TTL passes, and not rebuilt while the version is the same
+        tokio::time::advance(Duration::from_secs(299)).await;
+        assert!(Arc::ptr_eq(&first, &store.config().await.unwrap()));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(Arc::ptr_eq(&first, &store.config().await.unwrap()));
+
+        tokio::time::advance(Duration::from_secs(300)).await;
+        let second = store.config().await.unwrap();
+        assert_eq!(90, default_retention(&second));
+        assert!(second.policy.exclusions.check("/aws/vendedlogs/abc", "").is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keeps_last_good_config() {
+        let mut sequence = Sequence::new();
+        let mut mock_ssm = MockGetParameter::new();
+        mock_ssm
+            .expect_get_parameter()
+            .once()
+            .in_sequence(&mut sequence)
+            .returning(|_| parameter(1, r#"{"log_retention_in_days": 14}"#));
+        mock_ssm
+            .expect_get_parameter()
+            .once()
+            .in_sequence(&mut sequence)
+            .returning(|_| parameter(2, r#"{"log_retention_in_days": 45}"#));
+        mock_ssm
+            .expect_get_parameter()
+            .once()
+            .in_sequence(&mut sequence)
+            .returning(|_| Err(SsmError::ParameterNotFound(ParameterNotFound::builder().build())));
+
+        let store = PolicyStore::load(source(Some("/log-retention/policy")), mock_ssm).await.unwrap();
+        tokio::time::advance(Duration::from_secs(300)).await;
+        assert_eq!(14, default_retention(&store.config().await.unwrap()));
+        tokio::time::advance(Duration::from_secs(300)).await;
+        assert_eq!(14, default_retention(&store.config().await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_load_fails_on_invalid_parameter() {
+        let mut mock_ssm = MockGetParameter::new();
+        mock_ssm
+            .expect_get_parameter()
+            .once()
+            .returning(|_| parameter(1, r#"{"log_group_tags": "not a map"}"#));
+
+        let errors = PolicyStore::load(source(Some("/log-retention/policy")), mock_ssm).await.err().unwrap();
+
+        assert!(errors[0].message.contains("log_group_tags"));
+    }
+}
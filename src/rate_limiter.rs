@@ -0,0 +1,172 @@
+// Client-side throttling for the global retention setter, which can make thousands of calls per invocation.
+// The adaptive retry mode in `global::sdk_config` backs off after being throttled; this avoids being throttled in the first place.
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use aws_sdk_cloudwatchlogs::{
+    operation::{
+        delete_retention_policy::DeleteRetentionPolicyOutput, describe_log_groups::DescribeLogGroupsOutput, list_tags_for_resource::ListTagsForResourceOutput,
+        put_retention_policy::PutRetentionPolicyOutput, tag_resource::TagResourceOutput,
+    },
+    Error as CloudWatchLogsError,
+};
+use tokio::time::Instant;
+
+use crate::cloudwatch_logs_traits::{DeleteRetentionPolicy, DescribeLogGroups, ListTagsForResource, PutRetentionPolicy, TagResource};
+
+/// Default CloudWatch Logs quotas in transactions per second, per account and region.
+/// See https://docs.aws.amazon.com/AmazonCloudWatch/latest/logs/cloudwatch_limits_cwl.html
+pub const DESCRIBE_LOG_GROUPS_TPS: u32 = 10;
+pub const LIST_TAGS_FOR_RESOURCE_TPS: u32 = 10;
+pub const PUT_RETENTION_POLICY_TPS: u32 = 5;
+pub const DELETE_RETENTION_POLICY_TPS: u32 = 5;
+pub const TAG_RESOURCE_TPS: u32 = 10;
+
+/// Spaces out calls so that no more than `transactions_per_second` start in any second
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn per_second(transactions_per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / transactions_per_second.max(1),
+            next_slot: Mutex::new(None),
+        }
+    }
+
+    /// Waits until the next slot is free. Slots are handed out in the order callers arrive.
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().expect("Rate limiter lock poisoned");
+            let now = Instant::now();
+            let slot = next_slot.map_or(now, |next_slot| next_slot.max(now));
+            *next_slot = Some(slot + self.interval);
+            slot
+        };
+
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Wraps a CloudWatch Logs client, acquiring the per-operation rate limiter before each call
+#[derive(Debug)]
+pub struct RateLimitedCloudWatchLogs<C> {
+    inner: C,
+    describe_log_groups: RateLimiter,
+    list_tags_for_resource: RateLimiter,
+    put_retention_policy: RateLimiter,
+    delete_retention_policy: RateLimiter,
+    tag_resource: RateLimiter,
+}
+
+impl<C> RateLimitedCloudWatchLogs<C> {
+    /// Uses the default CloudWatch Logs quotas
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            describe_log_groups: RateLimiter::per_second(DESCRIBE_LOG_GROUPS_TPS),
+            list_tags_for_resource: RateLimiter::per_second(LIST_TAGS_FOR_RESOURCE_TPS),
+            put_retention_policy: RateLimiter::per_second(PUT_RETENTION_POLICY_TPS),
+            delete_retention_policy: RateLimiter::per_second(DELETE_RETENTION_POLICY_TPS),
+            tag_resource: RateLimiter::per_second(TAG_RESOURCE_TPS),
+        }
+    }
+}
+
+#[async_trait]
+impl<C: DescribeLogGroups + Send + Sync> DescribeLogGroups for RateLimitedCloudWatchLogs<C> {
+    async fn describe_log_groups(
+        &self,
+        log_group_name_prefix: Option<String>,
+        next_token: Option<String>,
+    ) -> Result<DescribeLogGroupsOutput, CloudWatchLogsError> {
+        self.describe_log_groups.acquire().await;
+        self.inner.describe_log_groups(log_group_name_prefix, next_token).await
+    }
+}
+
+#[async_trait]
+impl<C: ListTagsForResource + Send + Sync> ListTagsForResource for RateLimitedCloudWatchLogs<C> {
+    async fn list_tags_for_resource(&self, resource_arn: &str) -> Result<ListTagsForResourceOutput, CloudWatchLogsError> {
+        self.list_tags_for_resource.acquire().await;
+        self.inner.list_tags_for_resource(resource_arn).await
+    }
+}
+
+#[async_trait]
+impl<C: PutRetentionPolicy + Send + Sync> PutRetentionPolicy for RateLimitedCloudWatchLogs<C> {
+    async fn put_retention_policy(&self, log_group_name: &str, retention_in_days: i32) -> Result<PutRetentionPolicyOutput, CloudWatchLogsError> {
+        self.put_retention_policy.acquire().await;
+        self.inner.put_retention_policy(log_group_name, retention_in_days).await
+    }
+}
+
+#[async_trait]
+impl<C: DeleteRetentionPolicy + Send + Sync> DeleteRetentionPolicy for RateLimitedCloudWatchLogs<C> {
+    async fn delete_retention_policy(&self, log_group_name: &str) -> Result<DeleteRetentionPolicyOutput, CloudWatchLogsError> {
+        self.delete_retention_policy.acquire().await;
+        self.inner.delete_retention_policy(log_group_name).await
+    }
+}
+
+#[async_trait]
+impl<C: TagResource + Send + Sync> TagResource for RateLimitedCloudWatchLogs<C> {
+    async fn tag_resource(&self, log_group_arn: &str, tags: HashMap<String, String>) -> Result<TagResourceOutput, CloudWatchLogsError> {
+        self.tag_resource.acquire().await;
+        self.inner.tag_resource(log_group_arn, tags).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use aws_sdk_cloudwatchlogs::operation::describe_log_groups::DescribeLogGroupsOutput;
+    use tokio::time::Instant;
+
+    use crate::cloudwatch_logs_traits::{DescribeLogGroups, MockDescribeLogGroups};
+
+    use super::{RateLimitedCloudWatchLogs, RateLimiter};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_spaces_out_calls() {
+        let rate_limiter = RateLimiter::per_second(4);
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            rate_limiter.acquire().await;
+        }
+
+        assert_eq!(Duration::from_secs(1), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_first_call_is_immediate() {
+        let rate_limiter = RateLimiter::per_second(1);
+        let start = Instant::now();
+
+        rate_limiter.acquire().await;
+
+        assert_eq!(Duration::ZERO, start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limited_client_delegates() {
+        let mut mock = MockDescribeLogGroups::new();
+        mock.expect_describe_log_groups()
+            .times(11)
+            .returning(|_, _| Ok(DescribeLogGroupsOutput::builder().build()));
+        let client = RateLimitedCloudWatchLogs::new(mock);
+        let start = Instant::now();
+
+        for _ in 0..11 {
+            client.describe_log_groups(None, None).await.expect("Should not fail");
+        }
+
+        assert_eq!(Duration::from_secs(1), start.elapsed());
+    }
+}
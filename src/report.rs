@@ -0,0 +1,533 @@
+use std::{collections::HashMap, str::FromStr};
+
+use async_trait::async_trait;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    error::{Error, Severity},
+    s3_traits::PutObject,
+};
+
+#[cfg(test)]
+use mockall::automock;
+
+/// What the global sweep or a rollback did, or in dry-run mode would do, to one log group. Answers "what did the tool change last night?".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogGroupReport {
+    pub log_group_name: String,
+    pub log_group_arn: String,
+    /// Retention before the sweep or rollback. `0` means never expire.
+    pub previous_retention_in_days: i32,
+    /// Retention after the sweep or rollback. The same as `previous_retention_in_days` unless the group was changed.
+    pub retention_in_days: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// Tags put on the group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<HashMap<String, String>>,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The sweep or rollback was a dry run, so the group was left as it was
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Outcome {
+    Updated,
+    AlreadyHasRetention,
+    AlreadyTaggedWithRetention,
+    ReducedToMaximum,
+    RaisedToMinimum,
+    Excluded,
+    /// Rolled back to the retention recorded before this module changed it
+    Restored,
+    /// Rollback found the group already at its recorded retention
+    AlreadyRestored,
+    /// Rollback found the marker, but no record of the retention before it
+    NoChangeRecord,
+    Errored,
+}
+
+/// Somewhere to keep reports too large to return in the response
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait WriteReport {
+    /// Returns where the report was written
+    async fn write_report(&self, name: &str, reports: &[LogGroupReport]) -> Result<String, Error>;
+}
+
+#[async_trait]
+impl<W: WriteReport + Send + Sync + ?Sized> WriteReport for Box<W> {
+    async fn write_report(&self, name: &str, reports: &[LogGroupReport]) -> Result<String, Error> {
+        (**self).write_report(name, reports).await
+    }
+}
+
+/// Writes each entry as a line of JSON to stdout, which Lambda sends to the function's log group. Unlike `log` output, it is not filtered by `RUST_LOG`.
+#[derive(Debug, Clone, Default)]
+pub struct LogReportSink;
+
+#[async_trait]
+impl WriteReport for LogReportSink {
+    async fn write_report(&self, name: &str, reports: &[LogGroupReport]) -> Result<String, Error> {
+        for report in reports {
+            println!("{}", json!({"report": name, "logGroup": report}));
+        }
+        Ok(format!("logs (report `{}`)", name))
+    }
+}
+
+/// Uploads each report to `s3://<bucket>/<prefix><name>.<jsonl|csv>`
+#[derive(Debug, Clone)]
+pub struct S3ReportSink<P> {
+    s3: P,
+    bucket: String,
+    prefix: String,
+    format: ReportFormat,
+}
+
+impl<P: PutObject> S3ReportSink<P> {
+    pub fn new(s3: P, bucket: impl Into<String>, prefix: impl Into<String>, format: ReportFormat) -> Self {
+        Self {
+            s3,
+            bucket: bucket.into(),
+            prefix: prefix.into(),
+            format,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: PutObject + Send + Sync> WriteReport for S3ReportSink<P> {
+    async fn write_report(&self, name: &str, reports: &[LogGroupReport]) -> Result<String, Error> {
+        let key = format!("{}{}.{}", self.prefix, name, self.format.extension());
+        let location = format!("s3://{}/{}", self.bucket, key);
+
+        match self
+            .s3
+            .put_object(&self.bucket, &key, self.format.render(reports).into_bytes(), self.format.content_type())
+            .await
+        {
+            Ok(_) => Ok(location),
+            // The sweep itself succeeded, so a report which cannot be uploaded is only a warning
+            Err(e) => Err(Error {
+                message: format!("Could not upload report to {}: {}", location, e),
+                severity: Severity::Warning,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// One JSON object per line
+    #[default]
+    JsonLines,
+    Csv,
+}
+
+const CSV_HEADER: &str = "logGroupName,logGroupArn,previousRetentionInDays,retentionInDays,rule,tags,outcome,error,dryRun";
+
+impl ReportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::JsonLines => "jsonl",
+            ReportFormat::Csv => "csv",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ReportFormat::JsonLines => "application/x-ndjson",
+            ReportFormat::Csv => "text/csv",
+        }
+    }
+
+    pub fn render(&self, reports: &[LogGroupReport]) -> String {
+        let mut lines = vec![];
+        match self {
+            ReportFormat::JsonLines => lines.extend(reports.iter().map(|report| json!(report).to_string())),
+            ReportFormat::Csv => {
+                lines.push(CSV_HEADER.to_string());
+                lines.extend(reports.iter().map(csv_row));
+            }
+        }
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// Reads back a report written by `render`. JSON lines may also be the `{"report": ..., "logGroup": ...}` lines `LogReportSink` logs.
+    pub fn parse(&self, contents: &str) -> Result<Vec<LogGroupReport>, Error> {
+        let mut lines = contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        if *self == ReportFormat::Csv && lines.next().map(|(_, header)| header.trim()) != Some(CSV_HEADER) {
+            return Err(Error {
+                message: format!("CSV report must start with the header `{}`", CSV_HEADER),
+                severity: Severity::Error,
+            });
+        }
+
+        lines
+            .map(|(index, line)| {
+                let report = match self {
+                    ReportFormat::JsonLines => parse_json_line(line),
+                    ReportFormat::Csv => parse_csv_row(line),
+                };
+                report.map_err(|message| Error {
+                    message: format!("Could not read line {} of the report: {}", index + 1, message),
+                    severity: Severity::Error,
+                })
+            })
+            .collect()
+    }
+
+    /// `csv` for paths ending `.csv`, otherwise JSON lines
+    pub fn from_path(path: &str) -> Self {
+        match path.ends_with(".csv") {
+            true => ReportFormat::Csv,
+            false => ReportFormat::JsonLines,
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(Self::JsonLines),
+            "csv" => Ok(Self::Csv),
+            _ => Err(Error {
+                message: format!("Unknown report format `{}`. Expected one of jsonl, csv.", s),
+                severity: Severity::Error,
+            }),
+        }
+    }
+}
+
+// Tags are written as a JSON object in a single column
+fn csv_row(report: &LogGroupReport) -> String {
+    let outcome = json!(report.outcome);
+    let tags = report.tags.as_ref().map(|tags| json!(tags).to_string()).unwrap_or_default();
+    [
+        report.log_group_name.as_str(),
+        report.log_group_arn.as_str(),
+        &report.previous_retention_in_days.to_string(),
+        &report.retention_in_days.to_string(),
+        report.rule.as_deref().unwrap_or_default(),
+        &tags,
+        outcome.as_str().unwrap_or_default(),
+        report.error.as_deref().unwrap_or_default(),
+        if report.dry_run { "true" } else { "" },
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+fn parse_json_line(line: &str) -> Result<LogGroupReport, String> {
+    let mut value: JsonValue = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    if let Some(log_group) = value.get_mut("logGroup") {
+        value = log_group.take();
+    }
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+// The reverse of `csv_row`. Fields never span lines, since log group names, ARNs and tags cannot hold line breaks.
+fn parse_csv_row(line: &str) -> Result<LogGroupReport, String> {
+    let fields = split_csv_row(line);
+    let [log_group_name, log_group_arn, previous_retention_in_days, retention_in_days, rule, tags, outcome, error, dry_run] = fields.as_slice() else {
+        return Err(format!("expected 9 fields but found {}", fields.len()));
+    };
+    let optional = |field: &String| Some(field.clone()).filter(|field| !field.is_empty());
+
+    Ok(LogGroupReport {
+        log_group_name: log_group_name.clone(),
+        log_group_arn: log_group_arn.clone(),
+        previous_retention_in_days: previous_retention_in_days
+            .parse()
+            .map_err(|_| format!("invalid retention `{}`", previous_retention_in_days))?,
+        retention_in_days: retention_in_days.parse().map_err(|_| format!("invalid retention `{}`", retention_in_days))?,
+        rule: optional(rule),
+        tags: optional(tags).map(|tags| serde_json::from_str(&tags)).transpose().map_err(|e| e.to_string())?,
+        outcome: serde_json::from_value(json!(outcome)).map_err(|_| format!("unknown outcome `{}`", outcome))?,
+        error: optional(error),
+        dry_run: dry_run == "true",
+    })
+}
+
+fn split_csv_row(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("Always at least one field");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Returns reports of up to `inline_limit` entries in the response and writes larger ones to `sink`
+#[derive(Debug, Clone)]
+pub struct Reporter<W> {
+    sink: W,
+    inline_limit: usize,
+    always_write: bool,
+}
+
+impl<W: WriteReport> Reporter<W> {
+    pub fn new(sink: W, inline_limit: usize) -> Self {
+        Self {
+            sink,
+            inline_limit,
+            always_write: false,
+        }
+    }
+
+    /// Writes every report to the sink, even those small enough to return in the response
+    pub fn always_write(mut self) -> Self {
+        self.always_write = true;
+        self
+    }
+
+    /// Adds `report`, or where it was written, to `response`. A report which cannot be written does not fail the sweep.
+    pub async fn deliver(&self, name: &str, reports: Vec<LogGroupReport>, response: &mut JsonValue) {
+        let inline = reports.len() <= self.inline_limit;
+        if inline {
+            response["report"] = json!(reports);
+        }
+        if inline && !self.always_write {
+            return;
+        }
+
+        match self.sink.write_report(name, &reports).await {
+            Ok(location) => response["reportLocation"] = json!(location),
+            Err(error) => {
+                warn!("Could not write report `{}`: {}", name, error);
+                response["reportError"] = json!(error.message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_s3::{operation::put_object::PutObjectOutput, types::error::NoSuchBucket, Error as S3Error};
+    use mockall::predicate;
+    use serde_json::json;
+
+    use crate::{
+        error::{Error, Severity},
+        s3_traits::MockPutObject,
+    };
+
+    use super::*;
+
+    fn report(log_group_name: &str) -> LogGroupReport {
+        LogGroupReport {
+            log_group_name: log_group_name.to_string(),
+            log_group_arn: format!("arn:aws:logs:us-west-2:123456789012:log-group:{}", log_group_name),
+            previous_retention_in_days: 0,
+            retention_in_days: 30,
+            rule: Some("default".to_string()),
+            tags: None,
+            outcome: Outcome::Updated,
+            error: None,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            json!({"logGroupName": "abc", "logGroupArn": "arn:aws:logs:us-west-2:123456789012:log-group:abc", "previousRetentionInDays": 0, "retentionInDays": 30, "rule": "default", "outcome": "updated"}),
+            serde_json::to_value(report("abc")).unwrap()
+        );
+
+        let mut planned = report("abc");
+        planned.dry_run = true;
+        assert_eq!(json!(true), serde_json::to_value(planned).unwrap()["dryRun"]);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_inline() {
+        let mut sink = MockWriteReport::new();
+        sink.expect_write_report().never();
+        let mut response = json!({"message": "Success"});
+
+        Reporter::new(sink, 1).deliver("sweep", vec![report("abc")], &mut response).await;
+
+        assert_eq!(json!([report("abc")]), response["report"]);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_to_sink() {
+        let mut sink = MockWriteReport::new();
+        sink.expect_write_report()
+            .with(predicate::eq("sweep"), predicate::eq(vec![report("abc"), report("def")]))
+            .once()
+            .returning(|_, _| Ok("s3://bucket/report.jsonl".to_string()));
+        let mut response = json!({"message": "Success"});
+
+        Reporter::new(sink, 1).deliver("sweep", vec![report("abc"), report("def")], &mut response).await;
+
+        assert_eq!(JsonValue::Null, response["report"]);
+        assert_eq!(json!("s3://bucket/report.jsonl"), response["reportLocation"]);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_sink_failure() {
+        let mut sink = MockWriteReport::new();
+        sink.expect_write_report().returning(|_, _| {
+            Err(Error {
+                message: "Access denied".to_string(),
+                severity: Severity::Warning,
+            })
+        });
+        let mut response = json!({"message": "Success"});
+
+        Reporter::new(sink, 0).deliver("sweep", vec![report("abc")], &mut response).await;
+
+        assert_eq!(json!("Access denied"), response["reportError"]);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_always_write() {
+        let mut sink = MockWriteReport::new();
+        sink.expect_write_report().once().returning(|_, _| Ok("s3://bucket/report.jsonl".to_string()));
+        let mut response = json!({"message": "Success"});
+
+        Reporter::new(sink, 1).always_write().deliver("sweep", vec![report("abc")], &mut response).await;
+
+        assert_eq!(json!([report("abc")]), response["report"]);
+        assert_eq!(json!("s3://bucket/report.jsonl"), response["reportLocation"]);
+    }
+
+    #[tokio::test]
+    async fn test_s3_report_sink() {
+        let mut s3 = MockPutObject::new();
+        s3.expect_put_object()
+            .with(
+                predicate::eq("audit-bucket"),
+                predicate::eq("log-retention/123456789012/us-west-2/2024-01-31T02:00:00Z.csv"),
+                predicate::always(),
+                predicate::eq("text/csv"),
+            )
+            .once()
+            .returning(|_, _, _, _| Ok(PutObjectOutput::builder().build()));
+
+        let location = S3ReportSink::new(s3, "audit-bucket", "log-retention/", ReportFormat::Csv)
+            .write_report("123456789012/us-west-2/2024-01-31T02:00:00Z", &[report("abc")])
+            .await
+            .unwrap();
+
+        assert_eq!("s3://audit-bucket/log-retention/123456789012/us-west-2/2024-01-31T02:00:00Z.csv", location);
+    }
+
+    #[tokio::test]
+    async fn test_s3_report_sink_failure_is_a_warning() {
+        let mut s3 = MockPutObject::new();
+        s3.expect_put_object()
+            .returning(|_, _, _, _| Err(S3Error::NoSuchBucket(NoSuchBucket::builder().build())));
+
+        let error = S3ReportSink::new(s3, "audit-bucket", "", ReportFormat::JsonLines)
+            .write_report("sweep", &[report("abc")])
+            .await
+            .unwrap_err();
+
+        assert_eq!(Severity::Warning, error.severity);
+        assert!(error.message.contains("s3://audit-bucket/sweep.jsonl"));
+    }
+
+    #[test]
+    fn test_render_json_lines() {
+        let rendered = ReportFormat::JsonLines.render(&[report("abc"), report("def")]);
+
+        let lines: Vec<LogGroupReport> = rendered.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(vec![report("abc"), report("def")], lines);
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let mut failed = report("abc");
+        failed.outcome = Outcome::Errored;
+        failed.rule = None;
+        failed.error = Some("Throttled, \"slow down\"".to_string());
+        failed.tags = Some(HashMap::from([("team".to_string(), "platform".to_string())]));
+
+        insta::assert_snapshot!(ReportFormat::Csv.render(&[report("def"), failed]));
+    }
+
+    #[test]
+    fn test_parse_reads_back_render() {
+        let mut failed = report("a,\"b\"");
+        failed.outcome = Outcome::Errored;
+        failed.rule = None;
+        failed.error = Some("Throttled, \"slow down\"".to_string());
+        failed.tags = Some(HashMap::from([("team".to_string(), "platform".to_string())]));
+        let mut planned = report("ghi");
+        planned.dry_run = true;
+        let reports = vec![report("def"), failed, planned];
+
+        for format in [ReportFormat::JsonLines, ReportFormat::Csv] {
+            assert_eq!(reports, format.parse(&format.render(&reports)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_logged_lines() {
+        let logged = format!("{}\n\n", json!({"report": "sweep", "logGroup": report("abc")}));
+
+        assert_eq!(vec![report("abc")], ReportFormat::JsonLines.parse(&logged).unwrap());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let error = ReportFormat::JsonLines.parse("{\"logGroupName\": \"abc\"}").unwrap_err();
+        assert!(error.message.starts_with("Could not read line 1 of the report"));
+
+        ReportFormat::Csv.parse("abc,def\n").unwrap_err();
+        let csv = format!("{}\nabc,arn,0,30,default,,renamed,,\n", CSV_HEADER);
+        assert!(ReportFormat::Csv.parse(&csv).unwrap_err().message.contains("unknown outcome `renamed`"));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ReportFormat::Csv, ReportFormat::from_path("reports/2024-01-31T02:00:00Z.csv"));
+        assert_eq!(ReportFormat::JsonLines, ReportFormat::from_path("reports/2024-01-31T02:00:00Z.jsonl"));
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(ReportFormat::JsonLines, "jsonl".parse().unwrap());
+        assert_eq!(ReportFormat::Csv, "csv".parse().unwrap());
+        "xml".parse::<ReportFormat>().unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_log_report_sink() {
+        let location = LogReportSink.write_report("sweep", &[report("abc")]).await.unwrap();
+
+        assert_eq!("logs (report `sweep`)", location);
+    }
+}
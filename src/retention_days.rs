@@ -0,0 +1,166 @@
+use std::{fmt::Display, str::FromStr};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::error::{Error, Severity};
+
+/// Values accepted by CloudWatch Logs. See https://docs.aws.amazon.com/AmazonCloudWatchLogs/latest/APIReference/API_PutRetentionPolicy.html#API_PutRetentionPolicy_RequestParameters
+pub const ALLOWED_RETENTION_DAYS: [i32; 22] = [
+    1, 3, 5, 7, 14, 30, 60, 90, 120, 150, 180, 365, 400, 545, 731, 1096, 1827, 2192, 2557, 2922, 3288, 3653,
+];
+
+/// A retention period which CloudWatch Logs will accept in PutRetentionPolicy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(into = "i32")]
+pub struct RetentionDays(i32);
+
+/// What to do when a configured retention is not one of `ALLOWED_RETENTION_DAYS`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidRetentionStrategy {
+    #[default]
+    Reject,
+    RoundUp,
+    RoundDown,
+    /// Rounds to the closest allowed value. Ties round up, as keeping logs longer is the safer mistake.
+    Nearest,
+}
+
+impl RetentionDays {
+    pub fn new(days: i32, strategy: InvalidRetentionStrategy) -> Result<Self, Error> {
+        if ALLOWED_RETENTION_DAYS.contains(&days) {
+            return Ok(Self(days));
+        }
+
+        let round_up = ALLOWED_RETENTION_DAYS.iter().copied().find(|allowed| *allowed > days);
+        let round_down = ALLOWED_RETENTION_DAYS.iter().copied().rev().find(|allowed| *allowed < days);
+
+        let rounded = match strategy {
+            InvalidRetentionStrategy::Reject => None,
+            InvalidRetentionStrategy::RoundUp => round_up,
+            InvalidRetentionStrategy::RoundDown => round_down,
+            InvalidRetentionStrategy::Nearest => match (round_down, round_up) {
+                (Some(down), Some(up)) if days - down < up - days => Some(down),
+                (_, Some(up)) => Some(up),
+                (down, None) => down,
+            },
+        };
+
+        match rounded {
+            Some(rounded) => {
+                warn!("Retention of {} days is not accepted by CloudWatch Logs. Using {} days instead.", days, rounded);
+                Ok(Self(rounded))
+            }
+            None => Err(Error {
+                message: format!(
+                    "Retention of {} days is not accepted by CloudWatch Logs (strategy: {:?}). Allowed values are: {:?}",
+                    days, strategy, ALLOWED_RETENTION_DAYS
+                ),
+                severity: Severity::Error,
+            }),
+        }
+    }
+
+    pub fn days(&self) -> i32 {
+        self.0
+    }
+}
+
+impl TryFrom<i32> for RetentionDays {
+    type Error = Error;
+
+    fn try_from(days: i32) -> Result<Self, Self::Error> {
+        Self::new(days, InvalidRetentionStrategy::Reject)
+    }
+}
+
+impl From<RetentionDays> for i32 {
+    fn from(retention: RetentionDays) -> Self {
+        retention.0
+    }
+}
+
+impl Display for RetentionDays {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for InvalidRetentionStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "round_up" => Ok(Self::RoundUp),
+            "round_down" => Ok(Self::RoundDown),
+            "nearest" => Ok(Self::Nearest),
+            _ => Err(Error {
+                message: format!(
+                    "Unknown invalid retention strategy `{}`. Expected one of reject, round_up, round_down, nearest.",
+                    s
+                ),
+                severity: Severity::Error,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Severity;
+
+    use super::{InvalidRetentionStrategy, RetentionDays, ALLOWED_RETENTION_DAYS};
+
+    #[test]
+    fn test_allowed_values_accepted_by_every_strategy() {
+        for days in ALLOWED_RETENTION_DAYS {
+            for strategy in [
+                InvalidRetentionStrategy::Reject,
+                InvalidRetentionStrategy::RoundUp,
+                InvalidRetentionStrategy::RoundDown,
+                InvalidRetentionStrategy::Nearest,
+            ] {
+                assert_eq!(days, RetentionDays::new(days, strategy).unwrap().days());
+            }
+        }
+    }
+
+    #[test]
+    fn test_reject() {
+        let error = RetentionDays::try_from(45).unwrap_err();
+
+        assert_eq!(Severity::Error, error.severity);
+        assert!(error.message.contains("45"));
+    }
+
+    #[test]
+    fn test_round_up() {
+        assert_eq!(60, RetentionDays::new(45, InvalidRetentionStrategy::RoundUp).unwrap().days());
+        assert_eq!(1, RetentionDays::new(0, InvalidRetentionStrategy::RoundUp).unwrap().days());
+        RetentionDays::new(4000, InvalidRetentionStrategy::RoundUp).unwrap_err();
+    }
+
+    #[test]
+    fn test_round_down() {
+        assert_eq!(30, RetentionDays::new(45, InvalidRetentionStrategy::RoundDown).unwrap().days());
+        assert_eq!(3653, RetentionDays::new(4000, InvalidRetentionStrategy::RoundDown).unwrap().days());
+        RetentionDays::new(0, InvalidRetentionStrategy::RoundDown).unwrap_err();
+    }
+
+    #[test]
+    fn test_nearest() {
+        assert_eq!(30, RetentionDays::new(40, InvalidRetentionStrategy::Nearest).unwrap().days());
+        assert_eq!(60, RetentionDays::new(45, InvalidRetentionStrategy::Nearest).unwrap().days()); // Tie rounds up
+        assert_eq!(60, RetentionDays::new(50, InvalidRetentionStrategy::Nearest).unwrap().days());
+        assert_eq!(3653, RetentionDays::new(9999, InvalidRetentionStrategy::Nearest).unwrap().days());
+        assert_eq!(1, RetentionDays::new(-5, InvalidRetentionStrategy::Nearest).unwrap().days());
+    }
+
+    #[test]
+    fn test_strategy_from_str() {
+        assert_eq!(InvalidRetentionStrategy::RoundUp, "round_up".parse().unwrap());
+        assert_eq!(InvalidRetentionStrategy::Nearest, "nearest".parse().unwrap());
+        "sideways".parse::<InvalidRetentionStrategy>().unwrap_err();
+    }
+}
@@ -0,0 +1,388 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use crate::{
+    dry_run::PlannedAction,
+    exclusions::Exclusions,
+    metric_publisher::MetricName,
+    retention_days::InvalidRetentionStrategy,
+    retention_limits::{LimitAdjustment, RetentionLimits},
+    retention_rules::{RetentionRules, RuleMatch},
+    retention_tag::{RetentionTag, RetentionTagKeys},
+    tagging::TaggingPolicy,
+};
+
+/// How log groups are treated, part of `Config` and shared by both Lambdas and the CLI
+#[derive(Debug, Clone)]
+pub struct DecisionPolicy {
+    pub retention_rules: RetentionRules,
+    pub retention_limits: RetentionLimits,
+    pub invalid_retention_strategy: InvalidRetentionStrategy,
+    pub retention_tag_keys: RetentionTagKeys,
+    pub tagging: TaggingPolicy,
+    pub exclusions: Exclusions,
+    /// Number of log groups worked on at once
+    pub concurrency: usize,
+}
+
+/// Everything `decide` needs to know about a log group
+#[derive(Debug, Clone)]
+pub struct LogGroupState<'a> {
+    pub name: &'a str,
+    /// Without the trailing `:*`
+    pub arn: &'a str,
+    /// `0` when the group never expires
+    pub retention_in_days: i32,
+    pub tags: Option<&'a HashMap<String, String>>,
+}
+
+/// What should happen to a single log group. Carried out by `retention_setter::apply_decision` unless in dry-run mode.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RetentionDecision {
+    Excluded {
+        reason: String,
+    },
+    AlreadyHasRetention {
+        retention_in_days: i32,
+    },
+    AlreadyTaggedWithRetention {
+        tag_key: String,
+        tag_value: String,
+    },
+    SetRetention {
+        rule_match: RuleMatch,
+        previous_retention_in_days: i32,
+    },
+    EnforceLimit {
+        /// The rule evaluated for a group without retention, whose tags are put on the group as for `SetRetention`.
+        /// None when the group kept its own retention.
+        rule_match: Option<RuleMatch>,
+        previous_retention_in_days: i32,
+        adjustment: LimitAdjustment,
+    },
+}
+
+impl RetentionDecision {
+    /// Why the group is left alone, or None if it is changed
+    pub fn skip_reason(&self) -> Option<String> {
+        match self {
+            RetentionDecision::Excluded { reason } => Some(reason.clone()),
+            RetentionDecision::AlreadyHasRetention { retention_in_days } => Some(format!("it has retention of {} days already", retention_in_days)),
+            RetentionDecision::AlreadyTaggedWithRetention { tag_key, tag_value } => Some(format!("tag `{}`=`{}` exists on it", tag_key, tag_value)),
+            RetentionDecision::SetRetention { .. } | RetentionDecision::EnforceLimit { .. } => None,
+        }
+    }
+
+    /// Every tag put on the group when the decision is carried out at `changed_at`, or None if the group is left alone
+    pub fn tags(&self, tagging: &TaggingPolicy, changed_at: SystemTime) -> Option<HashMap<String, String>> {
+        match self {
+            RetentionDecision::SetRetention {
+                rule_match,
+                previous_retention_in_days,
+            } => Some(tagging.retention_set(rule_match, *previous_retention_in_days, changed_at)),
+            RetentionDecision::EnforceLimit {
+                rule_match,
+                previous_retention_in_days,
+                ..
+            } => Some(tagging.limit_enforced(rule_match.as_ref(), *previous_retention_in_days, changed_at)),
+            RetentionDecision::Excluded { .. } | RetentionDecision::AlreadyHasRetention { .. } | RetentionDecision::AlreadyTaggedWithRetention { .. } => None,
+        }
+    }
+
+    pub fn plan(&self, log_group_name: &str, tagging: &TaggingPolicy, changed_at: SystemTime) -> PlannedAction {
+        match self {
+            RetentionDecision::SetRetention { rule_match, .. } => {
+                PlannedAction::set_retention(log_group_name, rule_match, self.tags(tagging, changed_at).unwrap_or_default())
+            }
+            RetentionDecision::EnforceLimit {
+                previous_retention_in_days,
+                adjustment,
+                ..
+            } => PlannedAction::enforce_limit(log_group_name, *previous_retention_in_days, *adjustment),
+            skip => PlannedAction::skip(log_group_name, skip.skip_reason().unwrap_or_default()),
+        }
+    }
+
+    /// The metric counting log groups with this decision
+    pub fn metric_name(&self) -> MetricName {
+        match self {
+            RetentionDecision::Excluded { .. } => MetricName::Excluded,
+            RetentionDecision::AlreadyHasRetention { .. } => MetricName::AlreadyHasRetention,
+            RetentionDecision::AlreadyTaggedWithRetention { .. } => MetricName::AlreadyTaggedWithRetention,
+            RetentionDecision::SetRetention { .. } => MetricName::Updated,
+            RetentionDecision::EnforceLimit {
+                adjustment: LimitAdjustment::ReducedToMaximum(_),
+                ..
+            } => MetricName::ReducedToMaximum,
+            RetentionDecision::EnforceLimit {
+                adjustment: LimitAdjustment::RaisedToMinimum(_),
+                ..
+            } => MetricName::RaisedToMinimum,
+        }
+    }
+}
+
+/// Decides what to do with a log group from its state and the policy alone, without calling any API.
+/// Every way of setting retention goes through here, so a new policy only needs adding once.
+pub fn decide(log_group: &LogGroupState, policy: &DecisionPolicy) -> RetentionDecision {
+    if let Some(reason) = policy.exclusions.check(log_group.name, log_group.arn) {
+        return RetentionDecision::Excluded { reason };
+    }
+
+    let retention_in_days = log_group.retention_in_days;
+    // Groups restored by a rollback keep the retention they were restored to, whatever their tags say
+    if let Some((key, value)) = policy.tagging.rolled_back_tag(log_group.tags) {
+        return match retention_in_days {
+            0 => RetentionDecision::AlreadyTaggedWithRetention {
+                tag_key: key.to_string(),
+                tag_value: value.to_string(),
+            },
+            _ => RetentionDecision::AlreadyHasRetention { retention_in_days },
+        };
+    }
+
+    // None when the group keeps its existing retention, unless that is outside the retention limits
+    let rule_match = match RetentionTag::parse(log_group.tags, &policy.retention_tag_keys, policy.invalid_retention_strategy) {
+        RetentionTag::Override(retention_in_days) => Some(RuleMatch::from_retention_tag(retention_in_days)),
+        RetentionTag::OptOut { .. } if retention_in_days != 0 => return RetentionDecision::AlreadyHasRetention { retention_in_days },
+        // The marker only opts out groups which still have the retention this module set. Once it is deleted the rules apply again.
+        RetentionTag::OptOut { key, value } if policy.tagging.is_marker(&key, &value) => Some(policy.retention_rules.evaluate(log_group.name)),
+        RetentionTag::OptOut { key, value } => {
+            return RetentionDecision::AlreadyTaggedWithRetention {
+                tag_key: key,
+                tag_value: value,
+            }
+        }
+        RetentionTag::Absent if retention_in_days != 0 => None,
+        RetentionTag::Absent => Some(policy.retention_rules.evaluate(log_group.name)),
+    };
+
+    let target_retention = rule_match.as_ref().map_or(retention_in_days, |rule_match| rule_match.retention_in_days.days());
+    if let Some(adjustment) = policy.retention_limits.check(log_group.name, target_retention) {
+        // A group already at the limit, e.g. one whose retention tag asks for more than the maximum, is not changed again
+        if adjustment.retention_in_days().days() == retention_in_days {
+            return RetentionDecision::AlreadyHasRetention { retention_in_days };
+        }
+        return RetentionDecision::EnforceLimit {
+            rule_match,
+            previous_retention_in_days: retention_in_days,
+            adjustment,
+        };
+    }
+
+    match rule_match {
+        Some(rule_match) if rule_match.retention_in_days.days() != retention_in_days => RetentionDecision::SetRetention {
+            rule_match,
+            previous_retention_in_days: retention_in_days,
+        },
+        _ => RetentionDecision::AlreadyHasRetention { retention_in_days },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::{
+        exclusions::Exclusions,
+        retention_days::{InvalidRetentionStrategy, RetentionDays},
+        retention_limits::{LimitAdjustment, MaximumRetention, MinimumRetention, RetentionLimits},
+        retention_rules::{RetentionRules, RuleMatch},
+        retention_tag::RetentionTagKeys,
+        tagging::TaggingPolicy,
+    };
+
+    use super::{decide, DecisionPolicy, LogGroupState, RetentionDecision};
+
+    fn policy() -> DecisionPolicy {
+        DecisionPolicy {
+            retention_rules: RetentionRules::new(vec![], RetentionDays::try_from(30).unwrap(), None),
+            retention_limits: RetentionLimits::default(),
+            invalid_retention_strategy: InvalidRetentionStrategy::Reject,
+            retention_tag_keys: RetentionTagKeys::default(),
+            tagging: TaggingPolicy::default(),
+            exclusions: Exclusions::default(),
+            concurrency: 1,
+        }
+    }
+
+    fn decide_for(retention_in_days: i32, tags: &[(&str, &str)], policy: &DecisionPolicy) -> RetentionDecision {
+        let tags: HashMap<String, String> = tags.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        let log_group = LogGroupState {
+            name: "/aws/lambda/abc",
+            arn: "arn:aws:logs:us-east-1:123:log-group:/aws/lambda/abc",
+            retention_in_days,
+            tags: Some(&tags),
+        };
+        decide(&log_group, policy)
+    }
+
+    #[test]
+    fn test_decide() {
+        let policy = policy();
+
+        assert_eq!(
+            RetentionDecision::SetRetention {
+                rule_match: policy.retention_rules.evaluate("/aws/lambda/abc"),
+                previous_retention_in_days: 0
+            },
+            decide_for(0, &[], &policy)
+        );
+        assert_eq!(RetentionDecision::AlreadyHasRetention { retention_in_days: 7 }, decide_for(7, &[], &policy));
+        assert_eq!(
+            RetentionDecision::SetRetention {
+                rule_match: RuleMatch::from_retention_tag(RetentionDays::try_from(14).unwrap()),
+                previous_retention_in_days: 7
+            },
+            decide_for(7, &[("retention", "14")], &policy)
+        );
+        assert_eq!(
+            RetentionDecision::AlreadyHasRetention { retention_in_days: 14 },
+            decide_for(14, &[("retention", "14")], &policy)
+        );
+        assert_eq!(
+            RetentionDecision::AlreadyTaggedWithRetention {
+                tag_key: "retention".to_string(),
+                tag_value: "never".to_string()
+            },
+            decide_for(0, &[("retention", "never")], &policy)
+        );
+        assert_eq!(
+            RetentionDecision::AlreadyHasRetention { retention_in_days: 7 },
+            decide_for(7, &[("retention", "never")], &policy)
+        );
+    }
+
+    #[test]
+    fn test_decide_marked_group_without_retention() {
+        let policy = policy();
+        let marker = ("retention", "Set by AWS Default Log Retention project.");
+
+        // The marker only keeps the retention this module set; once that is deleted the rules apply again
+        assert_eq!(
+            RetentionDecision::AlreadyHasRetention { retention_in_days: 30 },
+            decide_for(30, &[marker], &policy)
+        );
+        assert_eq!(
+            RetentionDecision::SetRetention {
+                rule_match: policy.retention_rules.evaluate("/aws/lambda/abc"),
+                previous_retention_in_days: 0
+            },
+            decide_for(0, &[marker], &policy)
+        );
+        // Any other value is the owner's opt-out
+        assert_eq!(
+            RetentionDecision::AlreadyTaggedWithRetention {
+                tag_key: "retention".to_string(),
+                tag_value: "never".to_string()
+            },
+            decide_for(0, &[("retention", "never")], &policy)
+        );
+    }
+
+    #[test]
+    fn test_decide_excluded_before_anything_else() {
+        let policy = DecisionPolicy {
+            exclusions: Exclusions::parse(r#"{"prefixes": ["/aws/lambda/"]}"#).unwrap(),
+            ..policy()
+        };
+
+        assert_eq!(
+            RetentionDecision::Excluded {
+                reason: "it is excluded by prefix `/aws/lambda/`".to_string()
+            },
+            decide_for(0, &[("retention", "14")], &policy)
+        );
+    }
+
+    #[test]
+    fn test_decide_enforces_limits() {
+        let policy = DecisionPolicy {
+            retention_limits: RetentionLimits {
+                maximum: Some(MaximumRetention::new(RetentionDays::try_from(365).unwrap(), vec![])),
+                minimum: None,
+            },
+            ..policy()
+        };
+
+        assert_eq!(
+            RetentionDecision::EnforceLimit {
+                rule_match: None,
+                previous_retention_in_days: 3653,
+                adjustment: LimitAdjustment::ReducedToMaximum(RetentionDays::try_from(365).unwrap())
+            },
+            decide_for(3653, &[], &policy)
+        );
+        // A retention tag asking for more than the maximum is reduced too
+        assert_eq!(
+            RetentionDecision::EnforceLimit {
+                rule_match: Some(RuleMatch::from_retention_tag(RetentionDays::try_from(3653).unwrap())),
+                previous_retention_in_days: 0,
+                adjustment: LimitAdjustment::ReducedToMaximum(RetentionDays::try_from(365).unwrap())
+            },
+            decide_for(0, &[("retention", "3653")], &policy)
+        );
+    }
+
+    #[test]
+    fn test_decide_leaves_groups_at_the_limit() {
+        let policy = DecisionPolicy {
+            retention_limits: RetentionLimits {
+                maximum: Some(MaximumRetention::new(RetentionDays::try_from(365).unwrap(), vec![])),
+                minimum: Some(MinimumRetention::new(RetentionDays::try_from(7).unwrap(), vec![])),
+            },
+            ..policy()
+        };
+
+        // The retention tag asks for more than the maximum, or less than the minimum, and the group was already moved to the limit
+        assert_eq!(
+            RetentionDecision::AlreadyHasRetention { retention_in_days: 365 },
+            decide_for(365, &[("retention", "3653")], &policy)
+        );
+        assert_eq!(
+            RetentionDecision::AlreadyHasRetention { retention_in_days: 7 },
+            decide_for(7, &[("retention", "1")], &policy)
+        );
+    }
+
+    #[test]
+    fn test_decide_enforces_limits_with_rule_tags() {
+        let policy = DecisionPolicy {
+            retention_rules: RetentionRules::new(
+                vec![],
+                RetentionDays::try_from(3653).unwrap(),
+                Some(HashMap::from([("team".to_string(), "platform".to_string())])),
+            ),
+            retention_limits: RetentionLimits {
+                maximum: Some(MaximumRetention::new(RetentionDays::try_from(365).unwrap(), vec![])),
+                minimum: None,
+            },
+            ..policy()
+        };
+
+        // A new group whose rule is above the maximum gets the rule's tags and the marker, as if the rule had set it
+        let tags = decide_for(0, &[], &policy)
+            .tags(&policy.tagging, UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+            .unwrap();
+        assert_eq!(Some(&"platform".to_string()), tags.get("team"));
+        assert_eq!(Some(&"Set by AWS Default Log Retention project.".to_string()), tags.get("retention"));
+    }
+
+    #[test]
+    fn test_skip_reason() {
+        assert_eq!(
+            Some("it has retention of 7 days already".to_string()),
+            RetentionDecision::AlreadyHasRetention { retention_in_days: 7 }.skip_reason()
+        );
+        assert_eq!(
+            None,
+            RetentionDecision::SetRetention {
+                rule_match: RuleMatch::from_retention_tag(RetentionDays::try_from(14).unwrap()),
+                previous_retention_in_days: 7
+            }
+            .skip_reason()
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use glob::Pattern;
+
+use crate::{
+    error::{Error, Severity},
+    retention_days::RetentionDays,
+};
+
+/// Optional ceiling on retention. Groups whose retention would exceed it are reduced, unless they match an exemption.
+#[derive(Debug, Clone)]
+pub struct MaximumRetention {
+    pub retention_in_days: RetentionDays,
+    exemptions: Vec<Pattern>,
+}
+
+impl MaximumRetention {
+    pub fn new(retention_in_days: RetentionDays, exemptions: Vec<Pattern>) -> Self {
+        Self { retention_in_days, exemptions }
+    }
+
+    /// Parses a JSON array of log group names or globs, e.g. `["/audit/*", "my-special-group"]`
+    pub fn parse_exemptions(json: &str) -> Result<Vec<Pattern>, Error> {
+        let exemptions: Vec<String> = serde_json::from_str(json).map_err(|e| Error {
+            message: format!("Could not parse maximum retention exemptions `{}`: {}", json, e),
+            severity: Severity::Error,
+        })?;
+
+        exemptions
+            .iter()
+            .map(|exemption| {
+                Pattern::new(exemption).map_err(|e| Error {
+                    message: format!("Maximum retention exemption `{}` is not a valid glob: {}", exemption, e),
+                    severity: Severity::Error,
+                })
+            })
+            .collect()
+    }
+
+    pub fn is_exempt(&self, log_group_name: &str) -> bool {
+        self.exemptions.iter().any(|exemption| exemption.matches(log_group_name))
+    }
+
+    /// Returns the ceiling if `retention_in_days` exceeds it and the group is not exempt.
+    /// `0` means "never expire", which always exceeds the ceiling.
+    pub fn ceiling_for(&self, log_group_name: &str, retention_in_days: i32) -> Option<RetentionDays> {
+        let exceeds = retention_in_days == 0 || retention_in_days > self.retention_in_days.days();
+        match exceeds && !self.is_exempt(log_group_name) {
+            true => Some(self.retention_in_days),
+            false => None,
+        }
+    }
+}
+
+/// Optional floor on retention for compliance. Groups whose retention would fall below it are raised.
+/// If `prefixes` is empty the floor applies to every group, otherwise only to groups whose name starts with one of them.
+#[derive(Debug, Clone)]
+pub struct MinimumRetention {
+    pub retention_in_days: RetentionDays,
+    prefixes: Vec<String>,
+}
+
+impl MinimumRetention {
+    pub fn new(retention_in_days: RetentionDays, prefixes: Vec<String>) -> Self {
+        Self { retention_in_days, prefixes }
+    }
+
+    /// Parses a JSON array of log group name prefixes, e.g. `["/aws/lambda/prod-", "/audit/"]`
+    pub fn parse_prefixes(json: &str) -> Result<Vec<String>, Error> {
+        serde_json::from_str(json).map_err(|e| Error {
+            message: format!("Could not parse minimum retention prefixes `{}`: {}", json, e),
+            severity: Severity::Error,
+        })
+    }
+
+    pub fn applies_to(&self, log_group_name: &str) -> bool {
+        self.prefixes.is_empty() || self.prefixes.iter().any(|prefix| log_group_name.starts_with(prefix))
+    }
+
+    /// Returns the floor if `retention_in_days` is below it and the group is covered.
+    /// `0` means "never expire", which is never below the floor.
+    pub fn floor_for(&self, log_group_name: &str, retention_in_days: i32) -> Option<RetentionDays> {
+        let below = retention_in_days != 0 && retention_in_days < self.retention_in_days.days();
+        match below && self.applies_to(log_group_name) {
+            true => Some(self.retention_in_days),
+            false => None,
+        }
+    }
+}
+
+/// A change forced by a maximum or minimum retention, regardless of what the rules or `retention` tag ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitAdjustment {
+    ReducedToMaximum(RetentionDays),
+    RaisedToMinimum(RetentionDays),
+}
+
+impl LimitAdjustment {
+    pub fn retention_in_days(&self) -> RetentionDays {
+        match self {
+            LimitAdjustment::ReducedToMaximum(retention_in_days) | LimitAdjustment::RaisedToMinimum(retention_in_days) => *retention_in_days,
+        }
+    }
+}
+
+/// The configured maximum and minimum retention. Both are optional.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionLimits {
+    pub maximum: Option<MaximumRetention>,
+    pub minimum: Option<MinimumRetention>,
+}
+
+impl RetentionLimits {
+    /// Returns the adjustment needed for a group which would otherwise end up with `retention_in_days`, if any
+    pub fn check(&self, log_group_name: &str, retention_in_days: i32) -> Option<LimitAdjustment> {
+        let maximum = self.maximum.as_ref().and_then(|maximum| maximum.ceiling_for(log_group_name, retention_in_days));
+        let minimum = self.minimum.as_ref().and_then(|minimum| minimum.floor_for(log_group_name, retention_in_days));
+
+        maximum.map(LimitAdjustment::ReducedToMaximum).or(minimum.map(LimitAdjustment::RaisedToMinimum))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::retention_days::RetentionDays;
+
+    use super::{LimitAdjustment, MaximumRetention, MinimumRetention, RetentionLimits};
+
+    fn maximum() -> MaximumRetention {
+        let exemptions = MaximumRetention::parse_exemptions(r#"["/audit/*", "keep-forever"]"#).unwrap();
+        MaximumRetention::new(RetentionDays::try_from(365).unwrap(), exemptions)
+    }
+
+    #[test]
+    fn test_ceiling_for() {
+        let ceiling = Some(RetentionDays::try_from(365).unwrap());
+
+        assert_eq!(ceiling, maximum().ceiling_for("/aws/lambda/abc", 3653));
+        assert_eq!(ceiling, maximum().ceiling_for("/aws/lambda/abc", 0));
+        assert_eq!(None, maximum().ceiling_for("/aws/lambda/abc", 365));
+        assert_eq!(None, maximum().ceiling_for("/aws/lambda/abc", 14));
+    }
+
+    #[test]
+    fn test_exemptions() {
+        assert_eq!(None, maximum().ceiling_for("/audit/payments", 3653));
+        assert_eq!(None, maximum().ceiling_for("keep-forever", 0));
+        assert!(maximum().ceiling_for("keep-forever-not", 0).is_some());
+    }
+
+    #[test]
+    fn test_parse_exemptions_invalid() {
+        MaximumRetention::parse_exemptions("not json").unwrap_err();
+        let error = MaximumRetention::parse_exemptions(r#"["[unclosed"]"#).unwrap_err();
+        assert!(error.message.contains("[unclosed"));
+    }
+
+    fn minimum(prefixes: &[&str]) -> MinimumRetention {
+        let prefixes = prefixes.iter().map(|prefix| prefix.to_string()).collect();
+        MinimumRetention::new(RetentionDays::try_from(30).unwrap(), prefixes)
+    }
+
+    #[test]
+    fn test_floor_for() {
+        let floor = Some(RetentionDays::try_from(30).unwrap());
+
+        assert_eq!(floor, minimum(&[]).floor_for("/aws/lambda/abc", 1));
+        assert_eq!(None, minimum(&[]).floor_for("/aws/lambda/abc", 30));
+        assert_eq!(None, minimum(&[]).floor_for("/aws/lambda/abc", 0));
+    }
+
+    #[test]
+    fn test_floor_for_prefixes() {
+        let minimum = minimum(&["/audit/", "/aws/lambda/prod-"]);
+
+        assert!(minimum.floor_for("/aws/lambda/prod-payments", 3).is_some());
+        assert_eq!(None, minimum.floor_for("/aws/lambda/dev-payments", 3));
+    }
+
+    #[test]
+    fn test_check() {
+        let limits = RetentionLimits {
+            maximum: Some(maximum()),
+            minimum: Some(minimum(&[])),
+        };
+
+        assert_eq!(
+            Some(LimitAdjustment::ReducedToMaximum(RetentionDays::try_from(365).unwrap())),
+            limits.check("abc", 3653)
+        );
+        assert_eq!(
+            Some(LimitAdjustment::RaisedToMinimum(RetentionDays::try_from(30).unwrap())),
+            limits.check("abc", 7)
+        );
+        assert_eq!(None, limits.check("abc", 90));
+        assert_eq!(None, RetentionLimits::default().check("abc", 0));
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use glob::Pattern;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Severity},
+    retention_days::{InvalidRetentionStrategy, RetentionDays},
+};
+
+pub const DEFAULT_RULE_NAME: &str = "default";
+
+/// An ordered list of retention rules. The first rule whose matcher accepts the log group name wins.
+/// If no rule matches, the default retention and tags are used.
+#[derive(Debug, Clone)]
+pub struct RetentionRules {
+    rules: Vec<RetentionRule>,
+    default_retention_in_days: RetentionDays,
+    default_tags: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionRule {
+    pub name: String,
+    pub matcher: Matcher,
+    pub retention_in_days: RetentionDays,
+    /// Tags to put on matching log groups. If not set, the default tags (`log_group_tags`) are used.
+    pub tags: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+/// The outcome of evaluating the rules against a log group name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleMatch {
+    pub rule: String,
+    pub retention_in_days: RetentionDays,
+    pub tags: Option<HashMap<String, String>>,
+}
+
+// Shape of a rule as it is written in configuration. Exactly one of `glob` or `regex` must be set.
+// Terraform's `jsonencode` emits `null` for unset optional attributes, so both fields are plain Options rather than an enum.
+#[derive(Debug, Deserialize)]
+struct RawRetentionRule {
+    name: String,
+    glob: Option<String>,
+    regex: Option<String>,
+    retention_in_days: i32,
+    tags: Option<HashMap<String, String>>,
+}
+
+impl RetentionRule {
+    fn from_raw(raw: RawRetentionRule, strategy: InvalidRetentionStrategy) -> Result<Self, Error> {
+        let matcher = match (raw.glob, raw.regex) {
+            (Some(glob), None) => {
+                Matcher::Glob(Pattern::new(&glob).map_err(|e| invalid_rule(format!("Rule `{}` has an invalid glob `{}`: {}", raw.name, glob, e)))?)
+            }
+            (None, Some(regex)) => {
+                Matcher::Regex(Regex::new(&regex).map_err(|e| invalid_rule(format!("Rule `{}` has an invalid regex `{}`: {}", raw.name, regex, e)))?)
+            }
+            _ => return Err(invalid_rule(format!("Rule `{}` must set exactly one of `glob` or `regex`", raw.name))),
+        };
+
+        let retention_in_days = RetentionDays::new(raw.retention_in_days, strategy)
+            .map_err(|e| invalid_rule(format!("Rule `{}` has an invalid retention: {}", raw.name, e.message)))?;
+
+        Ok(Self {
+            name: raw.name,
+            matcher,
+            retention_in_days,
+            tags: raw.tags,
+        })
+    }
+}
+
+fn invalid_rule(message: String) -> Error {
+    Error {
+        message,
+        severity: Severity::Error,
+    }
+}
+
+impl Matcher {
+    pub fn matches(&self, log_group_name: &str) -> bool {
+        match self {
+            Matcher::Glob(pattern) => pattern.matches(log_group_name),
+            Matcher::Regex(regex) => regex.is_match(log_group_name),
+        }
+    }
+}
+
+impl RetentionRules {
+    pub fn new(rules: Vec<RetentionRule>, default_retention_in_days: RetentionDays, default_tags: Option<HashMap<String, String>>) -> Self {
+        Self {
+            rules,
+            default_retention_in_days,
+            default_tags,
+        }
+    }
+
+    /// The same rules, falling back to `default_retention_in_days` instead when none match
+    pub fn with_default_retention_in_days(self, default_retention_in_days: RetentionDays) -> Self {
+        Self {
+            default_retention_in_days,
+            ..self
+        }
+    }
+
+    /// The same rules, tagging with `default_tags` instead when none match or the matching rule has no tags
+    pub fn with_default_tags(self, default_tags: Option<HashMap<String, String>>) -> Self {
+        Self { default_tags, ..self }
+    }
+
+    /// Parses a JSON array of rules, e.g. `[{"name": "lambda", "glob": "/aws/lambda/*", "retention_in_days": 14}]`
+    /// Retention values which CloudWatch Logs does not accept are handled according to `strategy`.
+    pub fn parse_rules(json: &str, strategy: InvalidRetentionStrategy) -> Result<Vec<RetentionRule>, Error> {
+        let raw_rules: Vec<RawRetentionRule> =
+            serde_json::from_str(json).map_err(|e| invalid_rule(format!("Could not parse retention rules `{}`: {}", json, e)))?;
+
+        raw_rules.into_iter().map(|raw| RetentionRule::from_raw(raw, strategy)).collect()
+    }
+
+    pub fn evaluate(&self, log_group_name: &str) -> RuleMatch {
+        match self.rules.iter().find(|rule| rule.matcher.matches(log_group_name)) {
+            Some(rule) => RuleMatch {
+                rule: rule.name.clone(),
+                retention_in_days: rule.retention_in_days,
+                tags: rule.tags.clone().or_else(|| self.default_tags.clone()),
+            },
+            None => RuleMatch {
+                rule: DEFAULT_RULE_NAME.to_string(),
+                retention_in_days: self.default_retention_in_days,
+                tags: self.default_tags.clone(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        error::Severity,
+        retention_days::{InvalidRetentionStrategy, RetentionDays},
+    };
+
+    use super::{RetentionRules, RuleMatch, DEFAULT_RULE_NAME};
+
+    fn days(days: i32) -> RetentionDays {
+        RetentionDays::try_from(days).unwrap()
+    }
+
+    fn rules() -> RetentionRules {
+        let json = r#"[
+            {"name": "lambda", "glob": "/aws/lambda/*", "retention_in_days": 14, "tags": {"team": "serverless"}},
+            {"name": "audit", "regex": "^/audit/(prod|prd)-", "retention_in_days": 3653, "tags": null},
+            {"name": "catch-lambda-again", "glob": "/aws/lambda/special*", "retention_in_days": 1}
+        ]"#;
+
+        let mut default_tags = HashMap::new();
+        default_tags.insert("a".to_string(), "b".to_string());
+
+        let rules = RetentionRules::parse_rules(json, InvalidRetentionStrategy::Reject).unwrap();
+        RetentionRules::new(rules, days(30), Some(default_tags))
+    }
+
+    #[test]
+    fn test_evaluate_glob() {
+        let result = rules().evaluate("/aws/lambda/my-function");
+
+        let mut expected_tags = HashMap::new();
+        expected_tags.insert("team".to_string(), "serverless".to_string());
+        let expected = RuleMatch {
+            rule: "lambda".to_string(),
+            retention_in_days: days(14),
+            tags: Some(expected_tags),
+        };
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_evaluate_first_match_wins() {
+        let result = rules().evaluate("/aws/lambda/special-function");
+        assert_eq!("lambda", result.rule);
+        assert_eq!(days(14), result.retention_in_days);
+    }
+
+    #[test]
+    fn test_evaluate_regex_falls_back_to_default_tags() {
+        let result = rules().evaluate("/audit/prod-payments");
+
+        assert_eq!("audit", result.rule);
+        assert_eq!(days(3653), result.retention_in_days);
+        assert_eq!(Some(&"b".to_string()), result.tags.unwrap().get("a"));
+    }
+
+    #[test]
+    fn test_evaluate_default() {
+        let result = rules().evaluate("/audit/dev-payments");
+
+        assert_eq!(DEFAULT_RULE_NAME, result.rule);
+        assert_eq!(days(30), result.retention_in_days);
+    }
+
+    #[test]
+    fn test_evaluate_no_rules() {
+        let result = RetentionRules::new(vec![], days(90), None).evaluate("anything");
+
+        let expected = RuleMatch {
+            rule: DEFAULT_RULE_NAME.to_string(),
+            retention_in_days: days(90),
+            tags: None,
+        };
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_parse_rules_invalid_regex() {
+        let error = RetentionRules::parse_rules(r#"[{"name": "bad", "regex": "(", "retention_in_days": 1}]"#, InvalidRetentionStrategy::Reject).unwrap_err();
+
+        assert_eq!(Severity::Error, error.severity);
+        assert!(error.message.contains("Rule `bad` has an invalid regex"));
+    }
+
+    #[test]
+    fn test_parse_rules_invalid_retention() {
+        let json = r#"[{"name": "typo", "glob": "*", "retention_in_days": 45}]"#;
+
+        let error = RetentionRules::parse_rules(json, InvalidRetentionStrategy::Reject).unwrap_err();
+        assert!(error.message.contains("Rule `typo` has an invalid retention"));
+
+        let rules = RetentionRules::parse_rules(json, InvalidRetentionStrategy::RoundDown).unwrap();
+        assert_eq!(days(30), rules[0].retention_in_days);
+    }
+
+    #[test]
+    fn test_parse_rules_requires_exactly_one_matcher() {
+        let error = RetentionRules::parse_rules(r#"[{"name": "neither", "retention_in_days": 1}]"#, InvalidRetentionStrategy::Reject).unwrap_err();
+        assert!(error.message.contains("must set exactly one of `glob` or `regex`"));
+
+        let error = RetentionRules::parse_rules(
+            r#"[{"name": "both", "glob": "*", "regex": ".*", "retention_in_days": 1}]"#,
+            InvalidRetentionStrategy::Reject,
+        )
+        .unwrap_err();
+        assert!(error.message.contains("must set exactly one of `glob` or `regex`"));
+    }
+}
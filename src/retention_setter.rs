@@ -1,6 +1,12 @@
+use std::time::SystemTime;
+
+use log::{debug, info};
+
 use crate::{
-    cloudwatch_logs_traits::DescribeLogGroups,
+    cloudwatch_logs_traits::{DescribeLogGroups, ListTagsForResource, PutRetentionPolicy, TagResource},
     error::{Error, Severity},
+    retention_decision::{decide, DecisionPolicy, LogGroupState, RetentionDecision},
+    tagging::TaggingPolicy,
 };
 
 pub async fn get_existing_retention(log_group_name: &str, client: &impl DescribeLogGroups) -> Result<i32, Error> {
@@ -23,6 +29,83 @@ pub async fn get_existing_retention(log_group_name: &str, client: &impl Describe
     }
 }
 
+/// Looks up what `decide` needs to know about a log group, decides, and carries out the decision unless `dry_run`.
+/// `retention_in_days` is looked up with DescribeLogGroups when None. `changed_at` is recorded in the tags of groups whose retention changes.
+pub async fn decide_and_apply(
+    log_group_name: &str,
+    log_group_arn: &str,
+    retention_in_days: Option<i32>,
+    client: &(impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource),
+    policy: &DecisionPolicy,
+    dry_run: bool,
+    changed_at: SystemTime,
+) -> Result<RetentionDecision, Error> {
+    // `decide` would exclude the group anyway; checking first saves the API calls
+    let decision = match policy.exclusions.check(log_group_name, log_group_arn) {
+        Some(reason) => RetentionDecision::Excluded { reason },
+        None => {
+            let retention_in_days = match retention_in_days {
+                Some(retention_in_days) => retention_in_days,
+                None => get_existing_retention(log_group_name, client).await?,
+            };
+            let tags = client.list_tags_for_resource(log_group_arn).await?;
+            let log_group = LogGroupState {
+                name: log_group_name,
+                arn: log_group_arn,
+                retention_in_days,
+                tags: tags.tags(),
+            };
+            decide(&log_group, policy)
+        }
+    };
+
+    if let Some(reason) = decision.skip_reason() {
+        debug!("Not setting retention for {} because {}.", log_group_name, reason);
+    } else if !dry_run {
+        apply_decision(&decision, log_group_name, log_group_arn, client, &policy.tagging, changed_at).await?;
+    }
+    Ok(decision)
+}
+
+/// Sets the decided retention and tags the group with `RetentionDecision::tags`. Decisions to skip the group do nothing.
+pub async fn apply_decision(
+    decision: &RetentionDecision,
+    log_group_name: &str,
+    log_group_arn: &str,
+    client: &(impl PutRetentionPolicy + TagResource),
+    tagging: &TaggingPolicy,
+    changed_at: SystemTime,
+) -> Result<(), Error> {
+    match decision {
+        RetentionDecision::SetRetention { rule_match, .. } => {
+            client.put_retention_policy(log_group_name, rule_match.retention_in_days.days()).await?;
+            info!(
+                "Set retention of {} days on {} (rule `{}`).",
+                rule_match.retention_in_days, log_group_name, rule_match.rule
+            );
+        }
+        RetentionDecision::EnforceLimit {
+            previous_retention_in_days,
+            adjustment,
+            ..
+        } => {
+            let retention_in_days = adjustment.retention_in_days();
+            client.put_retention_policy(log_group_name, retention_in_days.days()).await?;
+            info!(
+                "Changed retention of {} from {} to {} days to stay within retention limits.",
+                log_group_name, previous_retention_in_days, retention_in_days
+            );
+        }
+        RetentionDecision::Excluded { .. } | RetentionDecision::AlreadyHasRetention { .. } | RetentionDecision::AlreadyTaggedWithRetention { .. } => {}
+    }
+
+    if let Some(tags) = decision.tags(tagging, changed_at) {
+        client.tag_resource(log_group_arn, tags).await?;
+        info!("Tagged {}.", log_group_arn);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use aws_sdk_cloudwatchlogs::{operation::describe_log_groups::DescribeLogGroupsOutput, types::LogGroup, Error as CloudWatchLogsError};
@@ -90,7 +173,6 @@ mod tests {
         assert!(err.message.contains(group));
     }
 
-    #[allow(clippy::result_large_err)] // This is a test, don't care about large err type
     fn mock_describe_log_groups_response(log_group_name: &str, retention: i32) -> Result<DescribeLogGroupsOutput, CloudWatchLogsError> {
         let log_group = LogGroup::builder().log_group_name(log_group_name).retention_in_days(retention).build();
         let response = DescribeLogGroupsOutput::builder().log_groups(log_group).build();
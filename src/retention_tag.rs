@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use log::warn;
+
+use crate::{
+    retention_days::{InvalidRetentionStrategy, RetentionDays},
+    retention_rules::RuleMatch,
+};
+
+pub const RETENTION_TAG_KEY: &str = "retention";
+/// Value of the marker tag this module puts on groups it sets retention on
+pub const MARKER_TAG_VALUE: &str = "Set by AWS Default Log Retention project.";
+pub const RETENTION_TAG_RULE_NAME: &str = "retention tag";
+/// Default key of the tag recording the retention a group had before this module last changed it
+pub const PREVIOUS_RETENTION_TAG_KEY: &str = "previous-retention";
+/// Default key of the tag recording when this module last changed a group's retention, e.g. `2023-11-14T22:13:20Z`
+pub const RETENTION_CHANGED_AT_TAG_KEY: &str = "retention-changed-at";
+/// Default key of the tag put on groups a rollback restored, holding when it did. Any value opts the group out of both Lambdas.
+pub const ROLLED_BACK_AT_TAG_KEY: &str = "retention-rolled-back-at";
+/// Value of the previous retention tag for groups which had no retention (retention of `0`)
+pub const NEVER_EXPIRE: &str = "never-expire";
+
+/// The tags this module reads. Every binary takes them from here so that they treat tags the same way. The tags it writes are computed by `TaggingPolicy`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionTagKeys {
+    /// Holds a retention to keep the group at, or any other value to opt the group out. `retention` by default.
+    pub key: String,
+    /// Any value under one of these keys also opts the group out
+    pub additional_opt_out_keys: Vec<String>,
+}
+
+impl Default for RetentionTagKeys {
+    fn default() -> Self {
+        Self {
+            key: RETENTION_TAG_KEY.to_string(),
+            additional_opt_out_keys: vec![],
+        }
+    }
+}
+
+/// Meaning of the retention tag (`retention` unless configured otherwise) on a log group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetentionTag {
+    /// There is no retention tag on the group
+    Absent,
+    /// The tag holds a retention CloudWatch Logs accepts, e.g. `retention`=`14`. The group is kept at that value.
+    Override(RetentionDays),
+    /// Any other value, e.g. `never` or the marker this module adds, or any value under one of the additional opt-out keys. The group is left alone.
+    OptOut { key: String, value: String },
+}
+
+impl RetentionTag {
+    /// Shared by both Lambdas to interpret the tags returned by ListTagsForResource
+    pub fn parse(tags: Option<&HashMap<String, String>>, keys: &RetentionTagKeys, strategy: InvalidRetentionStrategy) -> Self {
+        let tags = match tags {
+            Some(tags) => tags,
+            None => return Self::Absent,
+        };
+        let opt_out = |key: &str, value: &str| Self::OptOut {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+
+        let value = match tags.get(&keys.key) {
+            Some(value) => value,
+            None => {
+                return match keys.additional_opt_out_keys.iter().find_map(|key| tags.get(key).map(|value| (key, value))) {
+                    Some((key, value)) => opt_out(key, value),
+                    None => Self::Absent,
+                }
+            }
+        };
+
+        let days = match value.trim().parse::<i32>() {
+            Ok(days) => days,
+            Err(_) => return opt_out(&keys.key, value),
+        };
+
+        match RetentionDays::new(days, strategy) {
+            Ok(retention) => Self::Override(retention),
+            Err(error) => {
+                warn!(
+                    "Tag `{}`=`{}` is not a retention CloudWatch Logs accepts; treating it as an opt-out. {}",
+                    keys.key, value, error.message
+                );
+                opt_out(&keys.key, value)
+            }
+        }
+    }
+}
+
+impl RuleMatch {
+    /// The decision for a group whose `retention` tag overrides the configured rules. No extra tags are applied.
+    pub fn from_retention_tag(retention_in_days: RetentionDays) -> Self {
+        Self {
+            rule: RETENTION_TAG_RULE_NAME.to_string(),
+            retention_in_days,
+            tags: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::retention_days::{InvalidRetentionStrategy, RetentionDays};
+
+    use super::{RetentionTag, RetentionTagKeys};
+
+    fn parse(value: Option<&str>, strategy: InvalidRetentionStrategy) -> RetentionTag {
+        let mut tags = HashMap::new();
+        tags.insert("team".to_string(), "abc".to_string());
+        if let Some(value) = value {
+            tags.insert("retention".to_string(), value.to_string());
+        }
+        RetentionTag::parse(Some(&tags), &RetentionTagKeys::default(), strategy)
+    }
+
+    fn opt_out(key: &str, value: &str) -> RetentionTag {
+        RetentionTag::OptOut {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_absent() {
+        assert_eq!(RetentionTag::Absent, parse(None, InvalidRetentionStrategy::Reject));
+        assert_eq!(
+            RetentionTag::Absent,
+            RetentionTag::parse(None, &RetentionTagKeys::default(), InvalidRetentionStrategy::Reject)
+        );
+    }
+
+    #[test]
+    fn test_override() {
+        assert_eq!(
+            RetentionTag::Override(RetentionDays::try_from(14).unwrap()),
+            parse(Some("14"), InvalidRetentionStrategy::Reject)
+        );
+        assert_eq!(
+            RetentionTag::Override(RetentionDays::try_from(365).unwrap()),
+            parse(Some(" 365 "), InvalidRetentionStrategy::Reject)
+        );
+    }
+
+    #[test]
+    fn test_opt_out() {
+        assert_eq!(opt_out("retention", "never"), parse(Some("never"), InvalidRetentionStrategy::Reject));
+        assert_eq!(
+            opt_out("retention", "Set by AWS Default Log Retention project."),
+            parse(Some("Set by AWS Default Log Retention project."), InvalidRetentionStrategy::Reject)
+        );
+    }
+
+    #[test]
+    fn test_invalid_number_uses_strategy() {
+        assert_eq!(opt_out("retention", "45"), parse(Some("45"), InvalidRetentionStrategy::Reject));
+        assert_eq!(
+            RetentionTag::Override(RetentionDays::try_from(60).unwrap()),
+            parse(Some("45"), InvalidRetentionStrategy::RoundUp)
+        );
+    }
+
+    #[test]
+    fn test_configured_keys() {
+        let keys = RetentionTagKeys {
+            key: "org:log-retention".to_string(),
+            additional_opt_out_keys: vec!["org:keep-logs".to_string()],
+        };
+        let parse = |tags: &[(&str, &str)]| {
+            let tags: HashMap<String, String> = tags.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+            RetentionTag::parse(Some(&tags), &keys, InvalidRetentionStrategy::Reject)
+        };
+
+        assert_eq!(RetentionTag::Absent, parse(&[("retention", "never")]));
+        assert_eq!(
+            RetentionTag::Override(RetentionDays::try_from(14).unwrap()),
+            parse(&[("org:log-retention", "14")])
+        );
+        assert_eq!(opt_out("org:log-retention", "never"), parse(&[("org:log-retention", "never")]));
+        assert_eq!(opt_out("org:keep-logs", "true"), parse(&[("org:keep-logs", "true")]));
+        // The retention tag wins over the additional opt-out keys
+        assert_eq!(
+            RetentionTag::Override(RetentionDays::try_from(14).unwrap()),
+            parse(&[("org:log-retention", "14"), ("org:keep-logs", "true")])
+        );
+    }
+}
@@ -0,0 +1,619 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use aws_sdk_cloudwatchlogs::types::LogGroup;
+use futures::{stream, StreamExt};
+use log::{debug, error, info};
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    cloudwatch_logs_traits::{DeleteRetentionPolicy, DescribeLogGroups, ListTagsForResource, PutRetentionPolicy, TagResource},
+    error::{Error, Severity},
+    report::{LogGroupReport, Outcome},
+    tagging::TaggingPolicy,
+};
+
+/// What a rollback should do. Rollbacks undo the changes this module made, e.g. after a bad rollout of new rules or limits.
+#[derive(Debug, Clone)]
+pub struct Rollback {
+    pub dry_run: bool,
+    /// Only roll back log groups whose names start with this
+    pub log_group_name_prefix: Option<String>,
+    /// Only roll back changes made at or after this time. None rolls back every recorded change.
+    pub changed_since: Option<SystemTime>,
+    /// How many log groups to work on at once
+    pub concurrency: usize,
+    /// When the rollback started, recorded on each restored group
+    pub started_at: SystemTime,
+}
+
+/// What to do with one log group when rolling back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollbackDecision {
+    /// Put back the retention the group had before this module changed it. `0` means never expire.
+    Restore { previous_retention_in_days: i32 },
+    /// The group already has the retention it had before the change
+    AlreadyRestored,
+    /// The group has the marker but no record of its previous retention, e.g. it was changed before changes were recorded
+    NoChangeRecord,
+    /// This module did not change the group, or not since `changed_since`
+    NotChanged,
+}
+
+/// Decides from the tags on a log group whose retention is `retention_in_days`, `0` meaning never expire
+pub fn decide_rollback(
+    tags: Option<&HashMap<String, String>>,
+    retention_in_days: i32,
+    tagging: &TaggingPolicy,
+    changed_since: Option<SystemTime>,
+) -> RollbackDecision {
+    let record = match tagging.change_record(tags) {
+        Some(record) => record,
+        None if tagging.is_marked(tags) => return RollbackDecision::NoChangeRecord,
+        None => return RollbackDecision::NotChanged,
+    };
+
+    // A change made at an unknown time may not be recent, so it is only rolled back without `changed_since`
+    let recent = match (changed_since, record.changed_at) {
+        (None, _) => true,
+        (Some(changed_since), Some(changed_at)) => changed_at >= changed_since,
+        (Some(_), None) => false,
+    };
+    if !recent {
+        return RollbackDecision::NotChanged;
+    }
+
+    match record.previous_retention_in_days == retention_in_days {
+        true => RollbackDecision::AlreadyRestored,
+        false => RollbackDecision::Restore {
+            previous_retention_in_days: record.previous_retention_in_days,
+        },
+    }
+}
+
+/// Puts back `previous_retention_in_days`. Groups which never expired have their retention policy deleted, since `0` cannot be put.
+/// The group is tagged as rolled back first, so that the event Lambda does not set retention again on the PutRetentionPolicy or DeleteRetentionPolicy event.
+pub async fn restore(
+    log_group_name: &str,
+    log_group_arn: &str,
+    previous_retention_in_days: i32,
+    rolled_back_tags: HashMap<String, String>,
+    client: &(impl PutRetentionPolicy + DeleteRetentionPolicy + TagResource),
+) -> Result<(), Error> {
+    client.tag_resource(log_group_arn, rolled_back_tags).await?;
+
+    match previous_retention_in_days {
+        0 => {
+            client.delete_retention_policy(log_group_name).await?;
+            info!("Deleted the retention policy of {}; it never expires again.", log_group_name);
+        }
+        days => {
+            client.put_retention_policy(log_group_name, days).await?;
+            info!("Restored retention of {} days on {}.", days, log_group_name);
+        }
+    }
+    Ok(())
+}
+
+/// Rolls back every log group visible to `cloudwatch_logs_client` which has a change recorded in its tags
+pub async fn rollback_from_tags(
+    cloudwatch_logs_client: impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + DeleteRetentionPolicy + TagResource,
+    rollback: &Rollback,
+    tagging: &TaggingPolicy,
+) -> Result<JsonValue, Error> {
+    let mut total_groups = 0;
+    let mut reports = vec![];
+
+    let mut next_token = None;
+    loop {
+        let result = cloudwatch_logs_client
+            .describe_log_groups(rollback.log_group_name_prefix.clone(), next_token.take())
+            .await?;
+
+        let results: Vec<_> = stream::iter(result.log_groups())
+            .map(|log_group| {
+                let client = &cloudwatch_logs_client;
+                async move { (log_group, rollback_log_group(log_group, client, rollback, tagging).await) }
+            })
+            .buffered(rollback.concurrency)
+            .collect()
+            .await;
+
+        for (log_group, result) in results {
+            total_groups += 1;
+            let retention_in_days = log_group.retention_in_days().unwrap_or(0);
+            let log_group_arn = log_group.arn().unwrap_or_default().replace(":*", "");
+            reports.extend(report(
+                log_group.log_group_name().unwrap_or_default(),
+                &log_group_arn,
+                retention_in_days,
+                &result,
+                rollback.dry_run,
+            ));
+        }
+
+        match result.next_token {
+            Some(token) => next_token = Some(token),
+            None => break,
+        }
+    }
+
+    Ok(respond(total_groups, reports, rollback.dry_run))
+}
+
+/// Rolls back the log groups a previous sweep's report says were changed, to the retention the report says they had before.
+/// The groups' tags are not read, so this also works for groups whose change was not recorded. Reports of dry runs are refused, since they changed nothing.
+pub async fn rollback_from_report(
+    cloudwatch_logs_client: impl PutRetentionPolicy + DeleteRetentionPolicy + TagResource,
+    rollback: &Rollback,
+    tagging: &TaggingPolicy,
+    reports: &[LogGroupReport],
+) -> Result<JsonValue, Error> {
+    if let Some(planned) = reports.iter().find(|report| report.dry_run) {
+        return Err(Error {
+            message: format!(
+                "Report is of a dry run, which did not change {} or any other group. Roll back using the report of a sweep which was not a dry run.",
+                planned.log_group_name
+            ),
+            severity: Severity::Error,
+        });
+    }
+
+    let changed = reports.iter().filter(|report| {
+        matches!(report.outcome, Outcome::Updated | Outcome::ReducedToMaximum | Outcome::RaisedToMinimum)
+            && rollback
+                .log_group_name_prefix
+                .as_ref()
+                .is_none_or(|prefix| report.log_group_name.starts_with(prefix))
+    });
+
+    let results: Vec<_> = stream::iter(changed)
+        .map(|changed| {
+            let client = &cloudwatch_logs_client;
+            async move {
+                let decision = RollbackDecision::Restore {
+                    previous_retention_in_days: changed.previous_retention_in_days,
+                };
+                let result = apply_rollback(&changed.log_group_name, &changed.log_group_arn, decision, client, rollback, tagging)
+                    .await
+                    .map(|_| decision);
+                (changed, result)
+            }
+        })
+        .buffered(rollback.concurrency)
+        .collect()
+        .await;
+
+    let total_groups = results.len();
+    let reports = results
+        .iter()
+        .filter_map(|(changed, result)| {
+            report(
+                &changed.log_group_name,
+                &changed.log_group_arn,
+                changed.retention_in_days,
+                result,
+                rollback.dry_run,
+            )
+        })
+        .collect();
+
+    Ok(respond(total_groups, reports, rollback.dry_run))
+}
+
+/// Works on one group of a DescribeLogGroups page, whose retention is already known
+async fn rollback_log_group(
+    log_group: &LogGroup,
+    client: &(impl ListTagsForResource + PutRetentionPolicy + DeleteRetentionPolicy + TagResource),
+    rollback: &Rollback,
+    tagging: &TaggingPolicy,
+) -> Result<RollbackDecision, Error> {
+    let log_group_arn = log_group.arn().expect("Log group ARN unexpectedly empty.").replace(":*", "");
+    let log_group_name = log_group.log_group_name().expect("Log group name unexpectedly empty.");
+
+    debug!("Working on {}", log_group_arn);
+    let tags = client.list_tags_for_resource(&log_group_arn).await?;
+    let decision = decide_rollback(tags.tags(), log_group.retention_in_days().unwrap_or(0), tagging, rollback.changed_since);
+    apply_rollback(log_group_name, &log_group_arn, decision, client, rollback, tagging).await?;
+    Ok(decision)
+}
+
+/// Restores the group if `decision` says to, unless `dry_run`. Other decisions do nothing.
+async fn apply_rollback(
+    log_group_name: &str,
+    log_group_arn: &str,
+    decision: RollbackDecision,
+    client: &(impl PutRetentionPolicy + DeleteRetentionPolicy + TagResource),
+    rollback: &Rollback,
+    tagging: &TaggingPolicy,
+) -> Result<(), Error> {
+    match decision {
+        RollbackDecision::Restore { previous_retention_in_days } if rollback.dry_run => {
+            info!(
+                "[DRY RUN] Would restore retention of {} to {} days.",
+                log_group_name, previous_retention_in_days
+            );
+            Ok(())
+        }
+        RollbackDecision::Restore { previous_retention_in_days } => {
+            restore(
+                log_group_name,
+                log_group_arn,
+                previous_retention_in_days,
+                tagging.rolled_back(rollback.started_at),
+                client,
+            )
+            .await
+        }
+        RollbackDecision::AlreadyRestored | RollbackDecision::NoChangeRecord | RollbackDecision::NotChanged => Ok(()),
+    }
+}
+
+/// None for groups which were not changed, which are left out of the report
+fn report(
+    log_group_name: &str,
+    log_group_arn: &str,
+    retention_in_days: i32,
+    result: &Result<RollbackDecision, Error>,
+    dry_run: bool,
+) -> Option<LogGroupReport> {
+    let mut report = LogGroupReport {
+        log_group_name: log_group_name.to_string(),
+        log_group_arn: log_group_arn.to_string(),
+        previous_retention_in_days: retention_in_days,
+        retention_in_days,
+        rule: None,
+        tags: None,
+        outcome: Outcome::Errored,
+        error: None,
+        dry_run,
+    };
+
+    match result {
+        Ok(RollbackDecision::Restore { previous_retention_in_days }) => {
+            report.outcome = Outcome::Restored;
+            report.retention_in_days = *previous_retention_in_days;
+        }
+        Ok(RollbackDecision::AlreadyRestored) => report.outcome = Outcome::AlreadyRestored,
+        Ok(RollbackDecision::NoChangeRecord) => report.outcome = Outcome::NoChangeRecord,
+        Ok(RollbackDecision::NotChanged) => return None,
+        Err(error) => {
+            error!("Failure rolling back retention of {}: {}", log_group_name, error);
+            report.error = Some(error.message.clone());
+        }
+    }
+    Some(report)
+}
+
+fn respond(total_groups: usize, reports: Vec<LogGroupReport>, dry_run: bool) -> JsonValue {
+    let count = |outcome: Outcome| reports.iter().filter(|report| report.outcome == outcome).count();
+    let errored = count(Outcome::Errored);
+
+    let mut response = json!({"totalGroups": total_groups, "restored": count(Outcome::Restored), "alreadyRestored": count(Outcome::AlreadyRestored), "noChangeRecord": count(Outcome::NoChangeRecord), "errored": errored, "report": reports});
+    if dry_run {
+        response["dryRun"] = json!(true);
+    }
+
+    // Groups which failed are counted in `errored` and listed in the report rather than failing the rollback, as for a sweep
+    response["message"] = match errored {
+        0 => json!("Success"),
+        _ => {
+            error!("Failed to roll back {} log group retentions", errored);
+            json!(format!("Failed to roll back {} log group retentions", errored))
+        }
+    };
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use async_trait::async_trait;
+    use aws_sdk_cloudwatchlogs::{
+        operation::{
+            delete_retention_policy::DeleteRetentionPolicyOutput, describe_log_groups::DescribeLogGroupsOutput,
+            list_tags_for_resource::ListTagsForResourceOutput, put_retention_policy::PutRetentionPolicyOutput, tag_resource::TagResourceOutput,
+        },
+        types::error::ResourceNotFoundException,
+        Error as CloudWatchLogsError,
+    };
+    use mockall::{mock, predicate, Sequence};
+
+    use super::*;
+
+    fn changed_at() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    fn tags(tags: &[(&str, &str)]) -> HashMap<String, String> {
+        tags.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    fn rollback(dry_run: bool) -> Rollback {
+        Rollback {
+            dry_run,
+            log_group_name_prefix: None,
+            changed_since: None,
+            concurrency: 10,
+            started_at: changed_at(),
+        }
+    }
+
+    fn log_group(log_group_name: &str, retention_in_days: i32) -> LogGroup {
+        LogGroup::builder()
+            .log_group_name(log_group_name)
+            .arn(format!("arn:aws:logs:us-west-2:123:log-group:{}:*", log_group_name))
+            .retention_in_days(retention_in_days)
+            .build()
+    }
+
+    fn rolled_back() -> HashMap<String, String> {
+        tags(&[("retention-rolled-back-at", "2023-11-14T22:13:20Z")])
+    }
+
+    fn changed(log_group_name: &str, previous_retention_in_days: i32, retention_in_days: i32, outcome: Outcome) -> LogGroupReport {
+        LogGroupReport {
+            log_group_name: log_group_name.to_string(),
+            log_group_arn: format!("arn:aws:logs:us-west-2:123:log-group:{}", log_group_name),
+            previous_retention_in_days,
+            retention_in_days,
+            rule: None,
+            tags: None,
+            outcome,
+            error: None,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_decide_rollback() {
+        let tagging = TaggingPolicy::default();
+        let recorded = tags(&[("previous-retention", "never-expire"), ("retention-changed-at", "2023-11-14T22:13:20Z")]);
+
+        assert_eq!(
+            RollbackDecision::Restore { previous_retention_in_days: 0 },
+            decide_rollback(Some(&recorded), 30, &tagging, None)
+        );
+        assert_eq!(RollbackDecision::AlreadyRestored, decide_rollback(Some(&recorded), 0, &tagging, None));
+        assert_eq!(
+            RollbackDecision::NoChangeRecord,
+            decide_rollback(Some(&tags(&[("retention", "Set by AWS Default Log Retention project.")])), 30, &tagging, None)
+        );
+        assert_eq!(
+            RollbackDecision::NotChanged,
+            decide_rollback(Some(&tags(&[("team", "platform")])), 30, &tagging, None)
+        );
+        assert_eq!(RollbackDecision::NotChanged, decide_rollback(None, 30, &tagging, None));
+    }
+
+    #[test]
+    fn test_decide_rollback_changed_since() {
+        let tagging = TaggingPolicy::default();
+        let recorded = tags(&[("previous-retention", "3653"), ("retention-changed-at", "2023-11-14T22:13:20Z")]);
+        let undated = tags(&[("previous-retention", "3653")]);
+
+        assert_eq!(
+            RollbackDecision::Restore {
+                previous_retention_in_days: 3653
+            },
+            decide_rollback(Some(&recorded), 365, &tagging, Some(changed_at()))
+        );
+        assert_eq!(
+            RollbackDecision::NotChanged,
+            decide_rollback(Some(&recorded), 365, &tagging, Some(changed_at() + Duration::from_secs(1)))
+        );
+        assert_eq!(RollbackDecision::NotChanged, decide_rollback(Some(&undated), 365, &tagging, Some(changed_at())));
+        assert!(matches!(decide_rollback(Some(&undated), 365, &tagging, None), RollbackDecision::Restore { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_rollback_from_tags() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(Some("/aws/".to_string())), predicate::eq(None))
+            .once()
+            .returning(|_, _| {
+                Ok(DescribeLogGroupsOutput::builder()
+                    .log_groups(log_group("/aws/never-expired", 30))
+                    .log_groups(log_group("/aws/was-long", 365))
+                    .log_groups(log_group("/aws/already-restored", 0))
+                    .log_groups(log_group("/aws/marked-only", 30))
+                    .log_groups(log_group("/aws/untouched", 14))
+                    .build())
+            });
+
+        let tags_by_group = HashMap::from([
+            ("/aws/never-expired", tags(&[("previous-retention", "never-expire")])),
+            ("/aws/was-long", tags(&[("previous-retention", "3653")])),
+            ("/aws/already-restored", tags(&[("previous-retention", "never-expire")])),
+            ("/aws/marked-only", tags(&[("retention", "Set by AWS Default Log Retention project.")])),
+            ("/aws/untouched", tags(&[("team", "platform")])),
+        ]);
+        mock_cloud_watch_logs_client.expect_list_tags_for_resource().returning(move |arn| {
+            let log_group_name = arn.rsplit(':').next().unwrap();
+            Ok(ListTagsForResourceOutput::builder()
+                .set_tags(tags_by_group.get(log_group_name).cloned())
+                .build())
+        });
+
+        // Restored groups are opted out before their retention changes, so that the event Lambda leaves them alone
+        let mut sequence = Sequence::new();
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq("arn:aws:logs:us-west-2:123:log-group:/aws/never-expired"),
+                predicate::eq(rolled_back()),
+            )
+            .once()
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_delete_retention_policy()
+            .with(predicate::eq("/aws/never-expired"))
+            .once()
+            .in_sequence(&mut sequence)
+            .returning(|_| Ok(DeleteRetentionPolicyOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq("arn:aws:logs:us-west-2:123:log-group:/aws/was-long"),
+                predicate::eq(rolled_back()),
+            )
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("/aws/was-long"), predicate::eq(3653))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        let rollback = Rollback {
+            log_group_name_prefix: Some("/aws/".to_string()),
+            ..rollback(false)
+        };
+        let result = rollback_from_tags(mock_cloud_watch_logs_client, &rollback, &TaggingPolicy::default())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_from_tags_dry_run() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .returning(|_, _| Ok(DescribeLogGroupsOutput::builder().log_groups(log_group("/aws/never-expired", 30)).build()));
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().tags("previous-retention", "never-expire").build()));
+        mock_cloud_watch_logs_client.expect_delete_retention_policy().never();
+        mock_cloud_watch_logs_client.expect_put_retention_policy().never();
+        mock_cloud_watch_logs_client.expect_tag_resource().never();
+
+        let result = rollback_from_tags(mock_cloud_watch_logs_client, &rollback(true), &TaggingPolicy::default())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(json!(true), result["dryRun"]);
+        assert_eq!(json!(1), result["restored"]);
+        assert_eq!(json!(0), result["report"][0]["retentionInDays"]);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_from_report() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::eq("arn:aws:logs:us-west-2:123:log-group:/aws/lambda/deleted"), predicate::always())
+            .once()
+            .returning(|_, _| Err(CloudWatchLogsError::ResourceNotFoundException(ResourceNotFoundException::builder().build())));
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::always(), predicate::eq(rolled_back()))
+            .times(2)
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_delete_retention_policy()
+            .with(predicate::eq("/aws/lambda/set-by-rule"))
+            .once()
+            .returning(|_| Ok(DeleteRetentionPolicyOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("/aws/lambda/raised"), predicate::eq(3))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("/aws/lambda/deleted"), predicate::always())
+            .never();
+
+        let reports = vec![
+            changed("/aws/lambda/set-by-rule", 0, 30, Outcome::Updated),
+            changed("/aws/lambda/raised", 3, 90, Outcome::RaisedToMinimum),
+            changed("/aws/lambda/deleted", 3653, 365, Outcome::ReducedToMaximum),
+            changed("/aws/lambda/kept", 14, 14, Outcome::AlreadyHasRetention),
+            changed("/other/set-by-rule", 0, 30, Outcome::Updated),
+        ];
+        let rollback = Rollback {
+            log_group_name_prefix: Some("/aws/lambda/".to_string()),
+            ..rollback(false)
+        };
+
+        let result = rollback_from_report(mock_cloud_watch_logs_client, &rollback, &TaggingPolicy::default(), &reports)
+            .await
+            .expect("Should not fail");
+
+        insta::assert_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_from_dry_run_report() {
+        // A dry run changed nothing, so rolling back its report would change groups to a retention they may never have had
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client.expect_tag_resource().never();
+        mock_cloud_watch_logs_client.expect_put_retention_policy().never();
+        mock_cloud_watch_logs_client.expect_delete_retention_policy().never();
+        let reports = vec![LogGroupReport {
+            dry_run: true,
+            ..changed("/aws/lambda/planned", 0, 30, Outcome::Updated)
+        }];
+
+        let error = rollback_from_report(mock_cloud_watch_logs_client, &rollback(false), &TaggingPolicy::default(), &reports)
+            .await
+            .unwrap_err();
+
+        assert_eq!(Severity::Error, error.severity);
+        assert!(error.message.contains("dry run"));
+    }
+
+    // Required to mock multiple traits at a time
+    // See https://docs.rs/mockall/latest/mockall/#multiple-and-inherited-traits
+    mock! {
+        pub CloudWatchLogs {}
+
+        #[async_trait]
+        impl DescribeLogGroups for CloudWatchLogs {
+            async fn describe_log_groups(
+                &self,
+                log_group_name_prefix: Option<String>,
+                next_token: Option<String>,
+            ) -> Result<DescribeLogGroupsOutput, CloudWatchLogsError>;
+        }
+
+        #[async_trait]
+        impl ListTagsForResource for CloudWatchLogs {
+            async fn list_tags_for_resource(
+                &self,
+                resource_arn: &str,
+            ) -> Result<ListTagsForResourceOutput, CloudWatchLogsError>;
+        }
+
+        #[async_trait]
+        impl PutRetentionPolicy for CloudWatchLogs {
+            async fn put_retention_policy(
+                &self,
+                log_group_name: &str,
+                retention_in_days: i32,
+            ) -> Result<PutRetentionPolicyOutput, CloudWatchLogsError>;
+        }
+
+        #[async_trait]
+        impl DeleteRetentionPolicy for CloudWatchLogs {
+            async fn delete_retention_policy(
+                &self,
+                log_group_name: &str,
+            ) -> Result<DeleteRetentionPolicyOutput, CloudWatchLogsError>;
+        }
+
+        #[async_trait]
+        impl TagResource for CloudWatchLogs {
+            async fn tag_resource(
+                &self,
+                log_group_arn: &str,
+                tags: HashMap<String, String>,
+            ) -> Result<TagResourceOutput, CloudWatchLogsError>;
+        }
+    }
+}
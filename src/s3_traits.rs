@@ -0,0 +1,51 @@
+// Traits defined for testing purposes -- see https://docs.aws.amazon.com/sdk-for-rust/latest/dg/testing.html
+
+use async_trait::async_trait;
+use aws_sdk_s3::{operation::put_object::PutObjectOutput, primitives::ByteStream, Client as S3Client, Error as S3Error};
+
+#[cfg(test)]
+use mockall::automock;
+
+/* Base Struct */
+
+#[derive(Clone, Debug)]
+pub struct S3 {
+    client: S3Client,
+}
+
+impl S3 {
+    pub fn new(client: S3Client) -> Self {
+        Self { client }
+    }
+}
+
+/* End Base Struct */
+
+/* Traits */
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait PutObject {
+    async fn put_object(&self, bucket: &str, key: &str, body: Vec<u8>, content_type: &str) -> Result<PutObjectOutput, S3Error>;
+}
+
+/* End Traits */
+
+/* Implementations */
+
+#[async_trait]
+impl PutObject for S3 {
+    async fn put_object(&self, bucket: &str, key: &str, body: Vec<u8>, content_type: &str) -> Result<PutObjectOutput, S3Error> {
+        Ok(self
+            .client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(ByteStream::from(body))
+            .content_type(content_type)
+            .send()
+            .await?)
+    }
+}
+
+/* End Implementations */
@@ -0,0 +1,43 @@
+// Traits defined for testing purposes -- see https://docs.aws.amazon.com/sdk-for-rust/latest/dg/testing.html
+
+use async_trait::async_trait;
+use aws_sdk_ssm::{operation::get_parameter::GetParameterOutput, Client as SsmClient, Error as SsmError};
+
+#[cfg(test)]
+use mockall::automock;
+
+/* Base Struct */
+
+#[derive(Clone, Debug)]
+pub struct Ssm {
+    client: SsmClient,
+}
+
+impl Ssm {
+    pub fn new(client: SsmClient) -> Self {
+        Self { client }
+    }
+}
+
+/* End Base Struct */
+
+/* Traits */
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait GetParameter {
+    async fn get_parameter(&self, name: &str) -> Result<GetParameterOutput, SsmError>;
+}
+
+/* End Traits */
+
+/* Implementations */
+
+#[async_trait]
+impl GetParameter for Ssm {
+    async fn get_parameter(&self, name: &str) -> Result<GetParameterOutput, SsmError> {
+        Ok(self.client.get_parameter().name(name).with_decryption(true).send().await?)
+    }
+}
+
+/* End Implementations */
@@ -0,0 +1,43 @@
+// Traits defined for testing purposes -- see https://docs.aws.amazon.com/sdk-for-rust/latest/dg/testing.html
+
+use async_trait::async_trait;
+use aws_sdk_sts::{operation::assume_role::AssumeRoleOutput, Client as StsClient, Error as StsError};
+
+#[cfg(test)]
+use mockall::automock;
+
+/* Base Struct */
+
+#[derive(Clone, Debug)]
+pub struct Sts {
+    client: StsClient,
+}
+
+impl Sts {
+    pub fn new(client: StsClient) -> Self {
+        Self { client }
+    }
+}
+
+/* End Base Struct */
+
+/* Traits */
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait AssumeRole {
+    async fn assume_role(&self, role_arn: &str, role_session_name: &str) -> Result<AssumeRoleOutput, StsError>;
+}
+
+/* End Traits */
+
+/* Implementations */
+
+#[async_trait]
+impl AssumeRole for Sts {
+    async fn assume_role(&self, role_arn: &str, role_session_name: &str) -> Result<AssumeRoleOutput, StsError> {
+        Ok(self.client.assume_role().role_arn(role_arn).role_session_name(role_session_name).send().await?)
+    }
+}
+
+/* End Implementations */
@@ -0,0 +1,1418 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use aws_sdk_cloudwatchlogs::types::LogGroup;
+use aws_smithy_types::date_time::{DateTime, Format};
+use futures::{stream, StreamExt};
+use lambda_runtime::LambdaEvent;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+use crate::{
+    cloudwatch_logs_traits::{DescribeLogGroups, ListTagsForResource, PutRetentionPolicy, TagResource},
+    dry_run::is_dry_run,
+    error::Error,
+    metric_publisher::{self, Metric, MetricName},
+    report::{LogGroupReport, Outcome, Reporter, WriteReport},
+    retention_decision::{DecisionPolicy, RetentionDecision},
+    retention_limits::LimitAdjustment,
+    retention_setter::decide_and_apply,
+    tagging::TaggingPolicy,
+};
+
+/// Stop starting new pages when less than this much time is left before the Lambda deadline
+const DEADLINE_MARGIN: Duration = Duration::from_secs(60);
+
+/// What a single invocation of the global retention setter should do.
+/// Large accounts may take several invocations to sweep; each returns a `nextToken` until the sweep is complete.
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    pub dry_run: bool,
+    /// Set when sweeping several accounts or regions, to tell their results and metrics apart
+    pub target: SweepTarget,
+    /// The Lambda's own account and region, used to name reports of sweeps with no `target`
+    pub home: SweepTarget,
+    /// Only sweep log groups whose names start with this
+    pub log_group_name_prefix: Option<String>,
+    /// `nextToken` returned by a previous invocation which ran out of time
+    pub next_token: Option<String>,
+    /// None means no time limit
+    pub deadline: Option<SystemTime>,
+    pub started_at: SystemTime,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            target: SweepTarget::default(),
+            home: SweepTarget::default(),
+            log_group_name_prefix: None,
+            next_token: None,
+            deadline: None,
+            started_at: SystemTime::now(),
+        }
+    }
+}
+
+impl Sweep {
+    /// `dry_run` is the configured setting, which the payload can turn on too
+    pub fn from_event(event: &LambdaEvent<JsonValue>, dry_run: bool) -> Self {
+        Self {
+            dry_run: is_dry_run(&event.payload, dry_run),
+            next_token: event.payload.get("nextToken").and_then(JsonValue::as_str).map(str::to_string),
+            deadline: Some(UNIX_EPOCH + Duration::from_millis(event.context.deadline)),
+            home: SweepTarget::from_function_arn(&event.context.invoked_function_arn),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_dimensions(&self, metric: Metric) -> Metric {
+        let mut metric = metric.with_dry_run(self.dry_run);
+        if let Some(account_id) = &self.target.account_id {
+            metric = metric.with_dimension("Account", account_id);
+        }
+        if let Some(region) = &self.target.region {
+            metric = metric.with_dimension("Region", region);
+        }
+        metric
+    }
+
+    /// Names this sweep's report by account, region and start time, e.g. `123456789012/us-east-1/2024-01-31T02:00:00Z`
+    pub fn report_name(&self) -> String {
+        let account_id = self.target.account_id.as_ref().or(self.home.account_id.as_ref());
+        let region = self.target.region.as_ref().or(self.home.region.as_ref());
+        format!(
+            "{}/{}/{}",
+            account_id.map_or("own", String::as_str),
+            region.map_or("own", String::as_str),
+            DateTime::from(self.started_at).fmt(Format::DateTime).unwrap_or_default()
+        )
+    }
+
+    pub fn out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| deadline.duration_since(SystemTime::now()).map_or(true, |remaining| remaining < DEADLINE_MARGIN))
+    }
+}
+
+/// One account and region to sweep. None means the Lambda's own account or region.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepTarget {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+impl SweepTarget {
+    /// The account and region of a Lambda ARN, `arn:<partition>:lambda:<region>:<account>:function:<name>`
+    pub fn from_function_arn(function_arn: &str) -> Self {
+        let parts: Vec<&str> = function_arn.split(':').collect();
+        let part = |index: usize| parts.get(index).filter(|part| !part.is_empty()).map(|part| part.to_string());
+        Self {
+            account_id: part(4),
+            region: part(3),
+        }
+    }
+
+    /// Every combination of `accounts` and `regions`
+    pub fn all(accounts: &[String], regions: &[String]) -> Vec<Self> {
+        let accounts: Vec<Option<String>> = match accounts.is_empty() {
+            true => vec![None],
+            false => accounts.iter().cloned().map(Some).collect(),
+        };
+        let regions: Vec<Option<String>> = match regions.is_empty() {
+            true => vec![None],
+            false => regions.iter().cloned().map(Some).collect(),
+        };
+
+        accounts
+            .iter()
+            .flat_map(|account_id| {
+                regions.iter().map(|region| SweepTarget {
+                    account_id: account_id.clone(),
+                    region: region.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Display for SweepTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "account {} in region {}",
+            self.account_id.as_deref().unwrap_or("(own)"),
+            self.region.as_deref().unwrap_or("(own)")
+        )
+    }
+}
+
+/// Sweeps every log group visible to `cloudwatch_logs_client`, or those starting with `sweep.log_group_name_prefix`, and publishes a summary of the outcome as metrics.
+/// Only a failure to list the log groups is an error; groups which fail are counted in `errored` and listed in the report.
+pub async fn process_all_log_groups(
+    cloudwatch_logs_client: impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource,
+    sweep: Sweep,
+    policy: &DecisionPolicy,
+    reporter: &Reporter<impl WriteReport>,
+) -> Result<JsonValue, Error> {
+    let dry_run = sweep.dry_run;
+    let mut errored = 0;
+    let mut total_groups = 0;
+    let mut updated = 0;
+    let mut already_has_retention = 0;
+    let mut already_tagged_with_retention = 0;
+    let mut reduced_to_maximum = 0;
+    let mut raised_to_minimum = 0;
+    let mut excluded = 0;
+    let mut updated_by_rule: HashMap<String, u64> = HashMap::new();
+    let mut plan = vec![];
+    let mut reports = vec![];
+
+    let mut next_token = sweep.next_token.clone();
+    loop {
+        let result = cloudwatch_logs_client
+            .describe_log_groups(sweep.log_group_name_prefix.clone(), next_token.take())
+            .await?;
+
+        // Up to `concurrency` groups are in flight at once. Results come back in page order, so counts and plans are deterministic.
+        let results: Vec<_> = stream::iter(result.log_groups())
+            .map(|log_group| {
+                let client = &cloudwatch_logs_client;
+                async move { (log_group, process_log_group(log_group, client, policy, dry_run, sweep.started_at).await) }
+            })
+            .buffered(policy.concurrency)
+            .collect()
+            .await;
+
+        for (log_group, result) in results {
+            total_groups += 1;
+            reports.push(report(log_group, &result, &policy.tagging, sweep.started_at, dry_run));
+            match result {
+                Ok(result) => {
+                    if dry_run {
+                        let planned_action = result.plan(log_group.log_group_name().unwrap_or_default(), &policy.tagging, sweep.started_at);
+                        info!("[DRY RUN] {}", planned_action);
+                        plan.push(planned_action);
+                    }
+
+                    match result {
+                        RetentionDecision::AlreadyHasRetention { .. } => already_has_retention += 1,
+                        RetentionDecision::AlreadyTaggedWithRetention { .. } => already_tagged_with_retention += 1,
+                        RetentionDecision::EnforceLimit { adjustment, .. } => match adjustment {
+                            LimitAdjustment::ReducedToMaximum(_) => reduced_to_maximum += 1,
+                            LimitAdjustment::RaisedToMinimum(_) => raised_to_minimum += 1,
+                        },
+                        RetentionDecision::Excluded { .. } => excluded += 1,
+                        RetentionDecision::SetRetention { rule_match, .. } => {
+                            updated += 1;
+                            *updated_by_rule.entry(rule_match.rule).or_default() += 1;
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failure updating retention of {}: {}", log_group.log_group_name().unwrap_or_default(), e);
+                    errored += 1;
+                }
+            }
+        }
+
+        match result.next_token {
+            Some(token) if sweep.out_of_time() => {
+                warn!("Stopping before the Lambda deadline. Invoke again with nextToken `{}` to resume.", token);
+                next_token = Some(token);
+                break;
+            }
+            Some(token) => next_token = Some(token),
+            None => break,
+        }
+    }
+
+    let metrics = vec![
+        Metric::new(MetricName::Total, total_groups),
+        Metric::new(MetricName::Updated, updated),
+        Metric::new(MetricName::AlreadyHasRetention, already_has_retention),
+        Metric::new(MetricName::AlreadyTaggedWithRetention, already_tagged_with_retention),
+        Metric::new(MetricName::ReducedToMaximum, reduced_to_maximum),
+        Metric::new(MetricName::RaisedToMinimum, raised_to_minimum),
+        Metric::new(MetricName::Excluded, excluded),
+        Metric::new(MetricName::Errored, errored),
+    ];
+    metric_publisher::publish_metrics(metrics.into_iter().map(|metric| sweep.with_dimensions(metric)).collect());
+
+    let mut response = json!({"totalGroups": total_groups, "updated": updated, "alreadyHasRetention": already_has_retention, "alreadyTaggedWithRetention": already_tagged_with_retention, "reducedToMaximum": reduced_to_maximum, "raisedToMinimum": raised_to_minimum, "excluded": excluded, "errored": errored, "updatedByRule": updated_by_rule, "complete": next_token.is_none(), "nextToken": next_token});
+    if dry_run {
+        response["dryRun"] = json!(true);
+        response["plan"] = json!(plan);
+    }
+    reporter.deliver(&sweep.report_name(), reports, &mut response).await;
+
+    // Groups which failed are counted in `errored` rather than failing the sweep, so that `nextToken` is still returned and the sweep can resume
+    response["message"] = match errored {
+        0 => json!("Success"),
+        _ => {
+            error!("Failed to update {} log group retentions", errored);
+            json!(format!("Failed to update {} log group retentions", errored))
+        }
+    };
+    Ok(response)
+}
+
+fn report(log_group: &LogGroup, result: &Result<RetentionDecision, Error>, tagging: &TaggingPolicy, changed_at: SystemTime, dry_run: bool) -> LogGroupReport {
+    let previous_retention_in_days = log_group.retention_in_days().unwrap_or(0);
+    let mut report = LogGroupReport {
+        log_group_name: log_group.log_group_name().unwrap_or_default().to_string(),
+        log_group_arn: log_group.arn().unwrap_or_default().replace(":*", ""),
+        previous_retention_in_days,
+        retention_in_days: previous_retention_in_days,
+        rule: None,
+        tags: None,
+        outcome: Outcome::Errored,
+        error: None,
+        dry_run,
+    };
+
+    match result {
+        Ok(RetentionDecision::AlreadyHasRetention { .. }) => report.outcome = Outcome::AlreadyHasRetention,
+        Ok(RetentionDecision::AlreadyTaggedWithRetention { .. }) => report.outcome = Outcome::AlreadyTaggedWithRetention,
+        Ok(decision @ RetentionDecision::SetRetention { rule_match, .. }) => {
+            report.outcome = Outcome::Updated;
+            report.retention_in_days = rule_match.retention_in_days.days();
+            report.rule = Some(rule_match.rule.clone());
+            report.tags = decision.tags(tagging, changed_at);
+        }
+        Ok(decision @ RetentionDecision::EnforceLimit { adjustment, .. }) => {
+            report.outcome = match adjustment {
+                LimitAdjustment::ReducedToMaximum(_) => Outcome::ReducedToMaximum,
+                LimitAdjustment::RaisedToMinimum(_) => Outcome::RaisedToMinimum,
+            };
+            report.retention_in_days = adjustment.retention_in_days().days();
+            report.tags = decision.tags(tagging, changed_at);
+        }
+        Ok(RetentionDecision::Excluded { .. }) => report.outcome = Outcome::Excluded,
+        Err(error) => report.error = Some(error.message.clone()),
+    }
+    report
+}
+
+/// Works on one group of a DescribeLogGroups page, whose retention is already known. Changes are recorded as made at `changed_at`, the start of the sweep.
+pub async fn process_log_group(
+    log_group: &LogGroup,
+    client: &(impl DescribeLogGroups + ListTagsForResource + PutRetentionPolicy + TagResource),
+    policy: &DecisionPolicy,
+    dry_run: bool,
+    changed_at: SystemTime,
+) -> Result<RetentionDecision, Error> {
+    let log_group_arn = log_group.arn().expect("Log group ARN unexpectedly empty.").replace(":*", ""); // Some ARNs (all ARNs?) have :* on the end, but list-tags-for-resource cannot accept that part
+    let log_group_name = log_group.log_group_name().expect("Log group name unexpectedly empty.");
+
+    debug!("Working on {}", log_group_arn);
+    decide_and_apply(
+        log_group_name,
+        &log_group_arn,
+        Some(log_group.retention_in_days().unwrap_or(0)),
+        client,
+        policy,
+        dry_run,
+        changed_at,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use mockall::{mock, predicate};
+
+    use async_trait::async_trait;
+    use aws_sdk_cloudwatchlogs::{
+        operation::{
+            describe_log_groups::DescribeLogGroupsOutput, list_tags_for_resource::ListTagsForResourceOutput, put_retention_policy::PutRetentionPolicyOutput,
+            tag_resource::TagResourceOutput,
+        },
+        types::{
+            error::{DataAlreadyAcceptedException, InvalidOperationException, ResourceAlreadyExistsException},
+            LogGroup,
+        },
+        Error as CloudWatchLogsError,
+    };
+
+    use crate::cloudwatch_logs_traits::{PutRetentionPolicy, TagResource};
+    use crate::exclusions::Exclusions;
+    use crate::report::MockWriteReport;
+    use crate::retention_days::{InvalidRetentionStrategy, RetentionDays};
+    use crate::retention_limits::{MaximumRetention, MinimumRetention, RetentionLimits};
+    use crate::retention_rules::{RetentionRules, RuleMatch};
+    use crate::retention_tag::RetentionTagKeys;
+
+    #[tokio::test]
+    async fn test_process_all_log_group_success() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(None), predicate::eq(None))
+            .returning(|_, _| {
+                Ok(DescribeLogGroupsOutput::builder()
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("MyLogGroupWasCreated")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+                            .retention_in_days(0)
+                            .build(),
+                    )
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("AnotherOneWithoutRetention")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/AnotherOneWithoutRetention:*")
+                            .retention_in_days(0)
+                            .build(),
+                    )
+                    .next_token("NextOnesPlease")
+                    .build())
+            });
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(None), predicate::eq(Some("NextOnesPlease".to_string())))
+            .returning(|_, _| {
+                Ok(DescribeLogGroupsOutput::builder()
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("SecondLogGroupAlreadyHasRetention")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/SecondLogGroupAlreadyHasRetention:*")
+                            .retention_in_days(90)
+                            .build(),
+                    )
+                    .build())
+            });
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(30))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("AnotherOneWithoutRetention"), predicate::eq(30))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated"),
+                predicate::eq(marked(&[("previous-retention", "never-expire")])),
+            )
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq("arn:aws:logs:123:us-west-2:log-group/AnotherOneWithoutRetention"),
+                predicate::eq(marked(&[("previous-retention", "never-expire")])),
+            )
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep(), &default_policy(), &inline_reporter())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_all_log_group_single_already_tagged_with_retention() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client.expect_describe_log_groups().returning(|_, _| {
+            Ok(DescribeLogGroupsOutput::builder()
+                .log_groups(
+                    LogGroup::builder()
+                        .log_group_name("MyLogGroupWasCreated")
+                        .arn("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails:*")
+                        .retention_in_days(0)
+                        .build(),
+                )
+                .build())
+        });
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().tags("retention", "DoNotTouch").build()));
+
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep(), &default_policy(), &inline_reporter())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_all_log_group_counts_excluded() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client.expect_describe_log_groups().returning(|_, _| {
+            Ok(DescribeLogGroupsOutput::builder()
+                .log_groups(
+                    LogGroup::builder()
+                        .log_group_name("/aws/vendedlogs/states/abc")
+                        .arn("arn:aws:logs:us-west-2:123:log-group:/aws/vendedlogs/states/abc:*")
+                        .retention_in_days(0)
+                        .build(),
+                )
+                .build())
+        });
+        // No API calls are made for excluded log groups
+        mock_cloud_watch_logs_client.expect_list_tags_for_resource().never();
+
+        let mut policy = default_policy();
+        policy.exclusions = Exclusions::parse(r#"{"prefixes": ["/aws/vendedlogs/"]}"#).unwrap();
+
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep(), &policy, &inline_reporter())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(json!(1), result["excluded"]);
+        assert_eq!(json!("excluded"), result["report"][0]["outcome"]);
+    }
+
+    #[tokio::test]
+    async fn test_process_all_log_group_partial_success() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(None), predicate::eq(None))
+            .returning(|_, _| {
+                Ok(DescribeLogGroupsOutput::builder()
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("MyLogGroupWasCreated")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+                            .retention_in_days(0)
+                            .build(),
+                    )
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("AnotherOneWithoutRetention")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/AnotherOneWithoutRetention:*")
+                            .retention_in_days(0)
+                            .build(),
+                    )
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("NoRetentionAndGetTagsCallFails")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails:*")
+                            .retention_in_days(0)
+                            .build(),
+                    )
+                    .next_token("MoreToCome")
+                    .build())
+            });
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(None), predicate::eq(Some("MoreToCome".to_string())))
+            .returning(|_, _| {
+                Ok(DescribeLogGroupsOutput::builder()
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("SecondLogGroupAlreadyHasRetention")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/SecondLogGroupAlreadyHasRetention:*")
+                            .retention_in_days(90)
+                            .build(),
+                    )
+                    .build())
+            });
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails"))
+            .returning(|_| {
+                Err(CloudWatchLogsError::DataAlreadyAcceptedException(
+                    DataAlreadyAcceptedException::builder().build(),
+                ))
+            });
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::ne("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails"))
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(30))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("AnotherOneWithoutRetention"), predicate::eq(30))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated"),
+                predicate::eq(marked(&[("previous-retention", "never-expire")])),
+            )
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq("arn:aws:logs:123:us-west-2:log-group/AnotherOneWithoutRetention"),
+                predicate::eq(marked(&[("previous-retention", "never-expire")])),
+            )
+            .once()
+            .returning(|_, _| Err(CloudWatchLogsError::InvalidOperationException(InvalidOperationException::builder().build())));
+
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep(), &default_policy(), &inline_reporter())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_all_log_group_errors_keep_next_token() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client.expect_describe_log_groups().once().returning(|_, _| {
+            Ok(DescribeLogGroupsOutput::builder()
+                .log_groups(
+                    LogGroup::builder()
+                        .log_group_name("GetTagsCallFails")
+                        .arn("arn:aws:logs:123:us-west-2:log-group/GetTagsCallFails:*")
+                        .build(),
+                )
+                .next_token("SecondPage")
+                .build())
+        });
+        mock_cloud_watch_logs_client.expect_list_tags_for_resource().returning(|_| {
+            Err(CloudWatchLogsError::DataAlreadyAcceptedException(
+                DataAlreadyAcceptedException::builder().build(),
+            ))
+        });
+
+        let sweep = Sweep {
+            deadline: Some(SystemTime::now()),
+            ..sweep()
+        };
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep, &default_policy(), &inline_reporter())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(json!(1), result["errored"]);
+        assert_eq!(json!(false), result["complete"]);
+        assert_eq!(json!("SecondPage"), result["nextToken"]);
+    }
+
+    #[tokio::test]
+    async fn test_process_all_log_group_dry_run() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client.expect_describe_log_groups().returning(|_, _| {
+            Ok(DescribeLogGroupsOutput::builder()
+                .log_groups(
+                    LogGroup::builder()
+                        .log_group_name("MyLogGroupWasCreated")
+                        .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+                        .build(),
+                )
+                .log_groups(
+                    LogGroup::builder()
+                        .log_group_name("TaggedToBeLeftAlone")
+                        .arn("arn:aws:logs:123:us-west-2:log-group/TaggedToBeLeftAlone:*")
+                        .build(),
+                )
+                .log_groups(
+                    LogGroup::builder()
+                        .log_group_name("SecondLogGroupAlreadyHasRetention")
+                        .arn("arn:aws:logs:123:us-west-2:log-group/SecondLogGroupAlreadyHasRetention:*")
+                        .retention_in_days(90)
+                        .build(),
+                )
+                .build())
+        });
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq("arn:aws:logs:123:us-west-2:log-group/TaggedToBeLeftAlone"))
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().tags("retention", "DoNotTouch").build()));
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client.expect_put_retention_policy().never();
+        mock_cloud_watch_logs_client.expect_tag_resource().never();
+
+        let sweep = Sweep { dry_run: true, ..sweep() };
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep, &default_policy(), &inline_reporter())
+            .await
+            .expect("Should not fail");
+
+        insta::assert_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn test_process_all_log_group_writes_large_report_to_sink() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client.expect_describe_log_groups().returning(|_, _| {
+            Ok(DescribeLogGroupsOutput::builder()
+                .log_groups(
+                    LogGroup::builder()
+                        .log_group_name("AlreadyHasRetention")
+                        .arn("arn:aws:logs:123:us-west-2:log-group/AlreadyHasRetention:*")
+                        .retention_in_days(90)
+                        .build(),
+                )
+                .build())
+        });
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        let mut sink = MockWriteReport::new();
+        sink.expect_write_report()
+            .withf(|name, reports| name.starts_with("own/own/") && reports.len() == 1 && reports[0].outcome == Outcome::AlreadyHasRetention)
+            .once()
+            .returning(|name, _| Ok(format!("s3://bucket/{}.jsonl", name)));
+
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep(), &default_policy(), &Reporter::new(sink, 0))
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(JsonValue::Null, result["report"]);
+        assert!(result["reportLocation"].as_str().unwrap().starts_with("s3://bucket/own/own/"));
+    }
+
+    #[tokio::test]
+    async fn test_process_all_log_group_stops_before_deadline() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(None), predicate::eq(None))
+            .once()
+            .returning(|_, _| {
+                Ok(DescribeLogGroupsOutput::builder()
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("FirstPage")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/FirstPage:*")
+                            .retention_in_days(90)
+                            .build(),
+                    )
+                    .next_token("SecondPage")
+                    .build())
+            });
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        let sweep = Sweep {
+            deadline: Some(SystemTime::now()),
+            ..sweep()
+        };
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep, &default_policy(), &inline_reporter())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(json!(false), result["complete"]);
+        assert_eq!(json!("SecondPage"), result["nextToken"]);
+        assert_eq!(json!(1), result["totalGroups"]);
+    }
+
+    #[tokio::test]
+    async fn test_process_all_log_group_resumes_from_next_token() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_describe_log_groups()
+            .with(predicate::eq(None), predicate::eq(Some("SecondPage".to_string())))
+            .once()
+            .returning(|_, _| {
+                Ok(DescribeLogGroupsOutput::builder()
+                    .log_groups(
+                        LogGroup::builder()
+                            .log_group_name("SecondPage")
+                            .arn("arn:aws:logs:123:us-west-2:log-group/SecondPage:*")
+                            .retention_in_days(90)
+                            .build(),
+                    )
+                    .build())
+            });
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        let sweep = Sweep {
+            next_token: Some("SecondPage".to_string()),
+            deadline: Some(SystemTime::now() + Duration::from_secs(900)),
+            ..sweep()
+        };
+        let result = process_all_log_groups(mock_cloud_watch_logs_client, sweep, &default_policy(), &inline_reporter())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(json!(true), result["complete"]);
+        assert_eq!(JsonValue::Null, result["nextToken"]);
+    }
+
+    fn target(account_id: Option<&str>, region: Option<&str>) -> SweepTarget {
+        SweepTarget {
+            account_id: account_id.map(str::to_string),
+            region: region.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_sweep_target_all() {
+        let accounts = vec!["111111111111".to_string(), "222222222222".to_string()];
+        let regions = vec!["us-east-1".to_string(), "us-west-2".to_string()];
+
+        assert_eq!(vec![target(None, None)], SweepTarget::all(&[], &[]));
+        assert_eq!(
+            vec![target(None, Some("us-east-1")), target(None, Some("us-west-2"))],
+            SweepTarget::all(&[], &regions)
+        );
+        assert_eq!(4, SweepTarget::all(&accounts, &regions).len());
+        assert_eq!(target(Some("222222222222"), Some("us-east-1")), SweepTarget::all(&accounts, &regions)[2]);
+    }
+
+    #[test]
+    fn test_sweep_from_event() {
+        let mut context = lambda_runtime::Context::default();
+        context.deadline = 1_700_000_000_000;
+        context.invoked_function_arn = "arn:aws:lambda:us-east-1:123456789012:function:global-log-retention-setter".to_string();
+        let event = LambdaEvent::new(json!({"nextToken": "abc", "dryRun": true}), context);
+
+        let sweep = Sweep::from_event(&event, false);
+
+        assert_eq!(Some("abc".to_string()), sweep.next_token);
+        assert!(sweep.dry_run);
+        assert!(sweep.out_of_time());
+        assert_eq!(target(Some("123456789012"), Some("us-east-1")), sweep.home);
+    }
+
+    #[test]
+    fn test_sweep_report_name() {
+        let sweep = Sweep {
+            target: target(Some("123456789012"), None),
+            home: target(Some("111111111111"), Some("us-east-1")),
+            started_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            ..sweep()
+        };
+
+        assert_eq!("123456789012/us-east-1/2023-11-14T22:13:20Z", sweep.report_name());
+        assert_eq!(SweepTarget::default(), SweepTarget::from_function_arn(""));
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_success() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        let log_group_arn = "arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated";
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq(log_group_arn))
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(30))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::eq(log_group_arn), predicate::eq(marked(&[("previous-retention", "never-expire")])))
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+            .retention_in_days(0)
+            .build();
+
+        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client, &default_policy(), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(
+            RetentionDecision::SetRetention {
+                rule_match: default_rules().evaluate("MyLogGroupWasCreated"),
+                previous_retention_in_days: 0
+            },
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_without_marker() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        let log_group_arn = "arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated";
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(30))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        let mut expected_tags = change_record("never-expire");
+        expected_tags.insert("team".to_string(), "platform".to_string());
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::eq(log_group_arn), predicate::eq(expected_tags))
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+            .build();
+        let rules = RetentionRules::new(
+            vec![],
+            RetentionDays::try_from(30).unwrap(),
+            Some(HashMap::from([("team".to_string(), "platform".to_string())])),
+        );
+        let mut policy = policy(rules, RetentionLimits::default());
+        policy.tagging = TaggingPolicy::without_marker();
+
+        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client, &policy, false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert!(matches!(result, RetentionDecision::SetRetention { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_uses_matching_rule() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        let log_group_arn = "arn:aws:logs:123:us-west-2:log-group:/aws/lambda/my-function";
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq(log_group_arn))
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("/aws/lambda/my-function"), predicate::eq(14))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq(log_group_arn),
+                predicate::eq(marked(&[("previous-retention", "never-expire"), ("team", "serverless")])),
+            )
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let rules = RetentionRules::parse_rules(
+            r#"[{"name": "lambda", "glob": "/aws/lambda/*", "retention_in_days": 14, "tags": {"team": "serverless"}}]"#,
+            InvalidRetentionStrategy::Reject,
+        )
+        .unwrap();
+        let rules = RetentionRules::new(rules, RetentionDays::try_from(30).unwrap(), Some(HashMap::new()));
+
+        let log_group = LogGroup::builder()
+            .log_group_name("/aws/lambda/my-function")
+            .arn("arn:aws:logs:123:us-west-2:log-group:/aws/lambda/my-function:*")
+            .build();
+
+        let result = process_log_group(
+            &log_group,
+            &mock_cloud_watch_logs_client,
+            &policy(rules.clone(), RetentionLimits::default()),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(
+            RetentionDecision::SetRetention {
+                rule_match: rules.evaluate("/aws/lambda/my-function"),
+                previous_retention_in_days: 0
+            },
+            result
+        );
+        assert_eq!("lambda", rules.evaluate("/aws/lambda/my-function").rule);
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_retention_already_set() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+            .retention_in_days(30)
+            .build();
+
+        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client, &default_policy(), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(RetentionDecision::AlreadyHasRetention { retention_in_days: 30 }, result);
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_retention_tag_overrides_existing_retention() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().tags("retention", "365").build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(365))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        // Retention from the retention tag is not marked, but the change is recorded
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(
+                predicate::eq("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated"),
+                predicate::eq(change_record("30")),
+            )
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+            .retention_in_days(30)
+            .build();
+
+        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client, &default_policy(), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(
+            RetentionDecision::SetRetention {
+                rule_match: RuleMatch::from_retention_tag(RetentionDays::try_from(365).unwrap()),
+                previous_retention_in_days: 30
+            },
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_retention_tag_already_enforced() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().tags("retention", "14").build()));
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+            .retention_in_days(14)
+            .build();
+
+        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client, &default_policy(), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(RetentionDecision::AlreadyHasRetention { retention_in_days: 14 }, result);
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_reduces_retention_above_maximum() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        let log_group_arn = "arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated";
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("MyLogGroupWasCreated"), predicate::eq(365))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        let expected_tags = change_record("3653");
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::eq(log_group_arn), predicate::eq(expected_tags))
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+            .retention_in_days(3653)
+            .build();
+        let retention_limits = RetentionLimits {
+            maximum: Some(MaximumRetention::new(RetentionDays::try_from(365).unwrap(), vec![])),
+            minimum: None,
+        };
+
+        let result = process_log_group(
+            &log_group,
+            &mock_cloud_watch_logs_client,
+            &policy(default_rules(), retention_limits),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(
+            RetentionDecision::EnforceLimit {
+                rule_match: None,
+                previous_retention_in_days: 3653,
+                adjustment: LimitAdjustment::ReducedToMaximum(RetentionDays::try_from(365).unwrap())
+            },
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_maximum_exemption() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client.expect_put_retention_policy().never();
+        mock_cloud_watch_logs_client.expect_tag_resource().never();
+
+        let log_group = LogGroup::builder()
+            .log_group_name("/audit/payments")
+            .arn("arn:aws:logs:123:us-west-2:log-group:/audit/payments:*")
+            .retention_in_days(3653)
+            .build();
+        let exemptions = MaximumRetention::parse_exemptions(r#"["/audit/*"]"#).unwrap();
+        let retention_limits = RetentionLimits {
+            maximum: Some(MaximumRetention::new(RetentionDays::try_from(365).unwrap(), exemptions)),
+            minimum: None,
+        };
+
+        let result = process_log_group(
+            &log_group,
+            &mock_cloud_watch_logs_client,
+            &policy(default_rules(), retention_limits),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(RetentionDecision::AlreadyHasRetention { retention_in_days: 3653 }, result);
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_raises_retention_below_minimum() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        let log_group_arn = "arn:aws:logs:123:us-west-2:log-group:/prod/payments";
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().build()));
+
+        mock_cloud_watch_logs_client
+            .expect_put_retention_policy()
+            .with(predicate::eq("/prod/payments"), predicate::eq(90))
+            .once()
+            .returning(|_, _| Ok(PutRetentionPolicyOutput::builder().build()));
+
+        let expected_tags = change_record("3");
+        mock_cloud_watch_logs_client
+            .expect_tag_resource()
+            .with(predicate::eq(log_group_arn), predicate::eq(expected_tags))
+            .once()
+            .returning(|_, _| Ok(TagResourceOutput::builder().build()));
+
+        let log_group = LogGroup::builder()
+            .log_group_name("/prod/payments")
+            .arn("arn:aws:logs:123:us-west-2:log-group:/prod/payments:*")
+            .retention_in_days(3)
+            .build();
+        let retention_limits = RetentionLimits {
+            maximum: None,
+            minimum: Some(MinimumRetention::new(RetentionDays::try_from(90).unwrap(), vec!["/prod/".to_string()])),
+        };
+
+        let result = process_log_group(
+            &log_group,
+            &mock_cloud_watch_logs_client,
+            &policy(default_rules(), retention_limits),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(
+            RetentionDecision::EnforceLimit {
+                rule_match: None,
+                previous_retention_in_days: 3,
+                adjustment: LimitAdjustment::RaisedToMinimum(RetentionDays::try_from(90).unwrap())
+            },
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_minimum_respects_opt_out() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .once()
+            .returning(|_| Ok(ListTagsForResourceOutput::builder().tags("retention", "short-lived on purpose").build()));
+
+        mock_cloud_watch_logs_client.expect_put_retention_policy().never();
+        mock_cloud_watch_logs_client.expect_tag_resource().never();
+
+        let log_group = LogGroup::builder()
+            .log_group_name("/prod/payments")
+            .arn("arn:aws:logs:123:us-west-2:log-group:/prod/payments:*")
+            .retention_in_days(3)
+            .build();
+        let retention_limits = RetentionLimits {
+            maximum: None,
+            minimum: Some(MinimumRetention::new(RetentionDays::try_from(90).unwrap(), vec![])),
+        };
+
+        let result = process_log_group(
+            &log_group,
+            &mock_cloud_watch_logs_client,
+            &policy(default_rules(), retention_limits),
+            false,
+            changed_at(),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(RetentionDecision::AlreadyHasRetention { retention_in_days: 3 }, result);
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_no_retention_but_tag_present() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+
+        mock_cloud_watch_logs_client.expect_list_tags_for_resource().once().returning(|_| {
+            Ok(ListTagsForResourceOutput::builder()
+                .tags("retention", "I know what I'm doing and I've tagged this group. Leave me alone!")
+                .build())
+        });
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+            .retention_in_days(0)
+            .build();
+
+        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client, &default_policy(), false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(
+            RetentionDecision::AlreadyTaggedWithRetention {
+                tag_key: "retention".to_string(),
+                tag_value: "I know what I'm doing and I've tagged this group. Leave me alone!".to_string()
+            },
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_additional_opt_out_key() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client.expect_list_tags_for_resource().once().returning(|_| {
+            Ok(ListTagsForResourceOutput::builder()
+                .tags("retention", "14")
+                .tags("org:keep-logs", "true")
+                .build())
+        });
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/MyLogGroupWasCreated:*")
+            .retention_in_days(0)
+            .build();
+        let mut policy = default_policy();
+        policy.retention_tag_keys = RetentionTagKeys {
+            key: "org:log-retention".to_string(),
+            additional_opt_out_keys: vec!["org:keep-logs".to_string()],
+        };
+
+        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client, &policy, false, changed_at())
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(
+            RetentionDecision::AlreadyTaggedWithRetention {
+                tag_key: "org:keep-logs".to_string(),
+                tag_value: "true".to_string()
+            },
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_log_group_fails() {
+        let mut mock_cloud_watch_logs_client = MockCloudWatchLogs::new();
+        mock_cloud_watch_logs_client
+            .expect_list_tags_for_resource()
+            .with(predicate::eq("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails"))
+            .once()
+            .returning(|_| {
+                // This type of error would never happen. Luckily it doesn't matter -- we only care that an error happened.
+                Err(CloudWatchLogsError::ResourceAlreadyExistsException(
+                    ResourceAlreadyExistsException::builder().build(),
+                ))
+            });
+
+        let log_group = LogGroup::builder()
+            .log_group_name("MyLogGroupWasCreated")
+            .arn("arn:aws:logs:123:us-west-2:log-group/NoRetentionAndGetTagsCallFails:*")
+            .retention_in_days(0)
+            .build();
+
+        let result = process_log_group(&log_group, &mock_cloud_watch_logs_client, &default_policy(), false, changed_at())
+            .await
+            .expect_err("Should fail");
+
+        insta::assert_debug_snapshot!(result);
+    }
+
+    fn changed_at() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    /// A sweep started at `changed_at()`, so that the change record is predictable
+    fn sweep() -> Sweep {
+        Sweep {
+            started_at: changed_at(),
+            ..Default::default()
+        }
+    }
+
+    /// The change-time tag recorded at `changed_at()`, plus `previous-retention`
+    fn change_record(previous_retention: &str) -> HashMap<String, String> {
+        HashMap::from([
+            ("previous-retention".to_string(), previous_retention.to_string()),
+            ("retention-changed-at".to_string(), "2023-11-14T22:13:20Z".to_string()),
+        ])
+    }
+
+    /// `tags` plus the default marker and change time, i.e. every tag sent with the default `TaggingPolicy`
+    fn marked(tags: &[(&str, &str)]) -> HashMap<String, String> {
+        let mut tags: HashMap<String, String> = tags.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        tags.insert("retention".to_string(), "Set by AWS Default Log Retention project.".to_string());
+        tags.insert("retention-changed-at".to_string(), "2023-11-14T22:13:20Z".to_string());
+        tags
+    }
+
+    fn default_rules() -> RetentionRules {
+        RetentionRules::new(vec![], RetentionDays::try_from(30).unwrap(), Some(HashMap::new()))
+    }
+
+    fn policy(retention_rules: RetentionRules, retention_limits: RetentionLimits) -> DecisionPolicy {
+        DecisionPolicy {
+            retention_rules,
+            retention_limits,
+            invalid_retention_strategy: InvalidRetentionStrategy::Reject,
+            retention_tag_keys: RetentionTagKeys::default(),
+            tagging: TaggingPolicy::default(),
+            exclusions: Exclusions::default(),
+            concurrency: 10,
+        }
+    }
+
+    fn default_policy() -> DecisionPolicy {
+        policy(default_rules(), RetentionLimits::default())
+    }
+
+    fn inline_reporter() -> Reporter<MockWriteReport> {
+        let mut sink = MockWriteReport::new();
+        sink.expect_write_report().never();
+        Reporter::new(sink, usize::MAX)
+    }
+
+    // Required to mock multiple traits at a time
+    // See https://docs.rs/mockall/latest/mockall/#multiple-and-inherited-traits
+    mock! {
+        // Creates MockCloudWatchLogs for use in tests
+        // Add more trait impls below if needed in tests
+        pub CloudWatchLogs {}
+
+        #[async_trait]
+        impl DescribeLogGroups for CloudWatchLogs {
+            async fn describe_log_groups(
+                &self,
+                log_group_name_prefix: Option<String>,
+                next_token: Option<String>,
+            ) -> Result<DescribeLogGroupsOutput, CloudWatchLogsError>;
+        }
+
+        #[async_trait]
+        impl PutRetentionPolicy for CloudWatchLogs {
+            async fn put_retention_policy(
+                &self,
+                log_group_name: &str,
+                retention_in_days: i32,
+            ) -> Result<PutRetentionPolicyOutput, CloudWatchLogsError>;
+        }
+
+        #[async_trait]
+        impl TagResource for CloudWatchLogs {
+            async fn tag_resource(
+                &self,
+                log_group_arn: &str,
+                tags: HashMap<String, String>,
+            ) -> Result<TagResourceOutput, CloudWatchLogsError>;
+        }
+
+        #[async_trait]
+        impl ListTagsForResource for CloudWatchLogs {
+            async fn list_tags_for_resource(
+                &self,
+                resource_arn: &str,
+            ) -> Result<ListTagsForResourceOutput, CloudWatchLogsError>;
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use aws_smithy_types::date_time::{DateTime, Format};
+
+use crate::{
+    retention_days::RetentionDays,
+    retention_rules::RuleMatch,
+    retention_tag::{MARKER_TAG_VALUE, NEVER_EXPIRE, PREVIOUS_RETENTION_TAG_KEY, RETENTION_CHANGED_AT_TAG_KEY, RETENTION_TAG_KEY, ROLLED_BACK_AT_TAG_KEY},
+};
+
+/// Computes every tag put on a log group whose retention this module changes. `TagResource` sends exactly these tags, nothing more.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggingPolicy {
+    /// `(key, value)` of the tag marking groups this module set retention on. None turns the marker off.
+    pub marker: Option<(String, String)>,
+    /// Key of the tag recording the retention a group had before this module last changed it. `previous-retention` by default.
+    pub previous_retention_key: String,
+    /// Key of the tag recording when this module last changed a group's retention. `retention-changed-at` by default.
+    pub changed_at_key: String,
+    /// Key of the tag put on groups a rollback restored. `retention-rolled-back-at` by default.
+    /// It is separate from the retention tag so that the group owner's own retention tag is kept.
+    pub rolled_back_key: String,
+}
+
+/// A change of retention recorded on a group by `TaggingPolicy::change_record_tags`, read back to roll the change back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeRecord {
+    /// Retention before the change. `0` means never expire.
+    pub previous_retention_in_days: i32,
+    /// None if the time is missing or cannot be read
+    pub changed_at: Option<SystemTime>,
+}
+
+impl Default for TaggingPolicy {
+    fn default() -> Self {
+        Self {
+            marker: Some((RETENTION_TAG_KEY.to_string(), MARKER_TAG_VALUE.to_string())),
+            previous_retention_key: PREVIOUS_RETENTION_TAG_KEY.to_string(),
+            changed_at_key: RETENTION_CHANGED_AT_TAG_KEY.to_string(),
+            rolled_back_key: ROLLED_BACK_AT_TAG_KEY.to_string(),
+        }
+    }
+}
+
+impl TaggingPolicy {
+    pub fn without_marker() -> Self {
+        Self {
+            marker: None,
+            ..Default::default()
+        }
+    }
+
+    /// Tags for a group given retention by `rule_match`: the rule's tags plus the marker, and the retention it had before and when it changed.
+    /// Only the change is recorded when the rule has no tags (e.g. `log_group_tags` is not set, or the retention came from the group's retention tag).
+    pub fn retention_set(&self, rule_match: &RuleMatch, previous_retention_in_days: i32, changed_at: SystemTime) -> HashMap<String, String> {
+        self.with_change_record(self.rule_tags(Some(rule_match)), previous_retention_in_days, changed_at)
+    }
+
+    /// Tags for a group moved within the retention limits. The same as `retention_set` for the rule evaluated for a group without retention,
+    /// if any; groups which kept their own retention only have the change recorded.
+    pub fn limit_enforced(&self, rule_match: Option<&RuleMatch>, previous_retention_in_days: i32, changed_at: SystemTime) -> HashMap<String, String> {
+        self.with_change_record(self.rule_tags(rule_match), previous_retention_in_days, changed_at)
+    }
+
+    /// Whether `key`=`value` is the marker, rather than a tag the group's owner set
+    pub fn is_marker(&self, key: &str, value: &str) -> bool {
+        self.marker
+            .as_ref()
+            .is_some_and(|(marker_key, marker_value)| marker_key == key && marker_value == value)
+    }
+
+    /// Whether `tags` include the marker, i.e. this module set retention on the group
+    pub fn is_marked(&self, tags: Option<&HashMap<String, String>>) -> bool {
+        match (&self.marker, tags) {
+            (Some((key, value)), Some(tags)) => tags.get(key) == Some(value),
+            _ => false,
+        }
+    }
+
+    /// Tags recording a change of retention, so that it can be audited and rolled back
+    pub fn change_record_tags(&self, previous_retention_in_days: i32, changed_at: SystemTime) -> HashMap<String, String> {
+        let previous_retention = match previous_retention_in_days {
+            0 => NEVER_EXPIRE.to_string(),
+            days => days.to_string(),
+        };
+        HashMap::from([
+            (self.previous_retention_key.clone(), previous_retention),
+            (self.changed_at_key.clone(), format_time(changed_at)),
+        ])
+    }
+
+    /// None when the group has no previous retention tag, or one holding a retention CloudWatch Logs would not have reported
+    pub fn change_record(&self, tags: Option<&HashMap<String, String>>) -> Option<ChangeRecord> {
+        let tags = tags?;
+        let previous_retention_in_days = match tags.get(&self.previous_retention_key)?.as_str() {
+            NEVER_EXPIRE => 0,
+            value => RetentionDays::try_from(value.trim().parse::<i32>().ok()?).ok()?.days(),
+        };
+        let changed_at = tags
+            .get(&self.changed_at_key)
+            .and_then(|value| DateTime::from_str(value, Format::DateTime).ok())
+            .and_then(|changed_at| SystemTime::try_from(changed_at).ok());
+
+        Some(ChangeRecord {
+            previous_retention_in_days,
+            changed_at,
+        })
+    }
+
+    /// The tag put on a group restored by a rollback at `rolled_back_at`
+    pub fn rolled_back(&self, rolled_back_at: SystemTime) -> HashMap<String, String> {
+        HashMap::from([(self.rolled_back_key.clone(), format_time(rolled_back_at))])
+    }
+
+    /// `(key, value)` of the rolled-back tag, if a rollback restored the group. Neither Lambda changes such groups again.
+    pub fn rolled_back_tag<'a>(&'a self, tags: Option<&'a HashMap<String, String>>) -> Option<(&'a str, &'a str)> {
+        let value = tags?.get(&self.rolled_back_key)?;
+        Some((&self.rolled_back_key, value))
+    }
+
+    // The record of the change wins over every other tag, so that it can always be rolled back
+    fn with_change_record(&self, mut tags: HashMap<String, String>, previous_retention_in_days: i32, changed_at: SystemTime) -> HashMap<String, String> {
+        tags.extend(self.change_record_tags(previous_retention_in_days, changed_at));
+        tags
+    }
+
+    // The marker only ever accompanies the rule's tags, and wins over a rule tag of the same key
+    fn rule_tags(&self, rule_match: Option<&RuleMatch>) -> HashMap<String, String> {
+        let mut tags = match rule_match.and_then(|rule_match| rule_match.tags.clone()) {
+            Some(tags) => tags,
+            None => return HashMap::new(),
+        };
+        if let Some((key, value)) = &self.marker {
+            tags.insert(key.clone(), value.clone());
+        }
+        tags
+    }
+}
+
+// e.g. `2023-11-14T22:13:20Z`
+fn format_time(time: SystemTime) -> String {
+    DateTime::from(time).fmt(Format::DateTime).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::{retention_days::RetentionDays, retention_rules::RuleMatch};
+
+    use super::{ChangeRecord, TaggingPolicy};
+
+    fn rule_match(tags: Option<&[(&str, &str)]>) -> RuleMatch {
+        RuleMatch {
+            rule: "default".to_string(),
+            retention_in_days: RetentionDays::try_from(30).unwrap(),
+            tags: tags.map(|tags| tags.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()),
+        }
+    }
+
+    fn tags(tags: &[(&str, &str)]) -> HashMap<String, String> {
+        tags.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    fn changed_at() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    #[test]
+    fn test_retention_set() {
+        let policy = TaggingPolicy::default();
+
+        assert_eq!(
+            tags(&[
+                ("team", "platform"),
+                ("retention", "Set by AWS Default Log Retention project."),
+                ("previous-retention", "never-expire"),
+                ("retention-changed-at", "2023-11-14T22:13:20Z")
+            ]),
+            policy.retention_set(&rule_match(Some(&[("team", "platform")])), 0, changed_at())
+        );
+        assert_eq!(
+            tags(&[
+                ("retention", "Set by AWS Default Log Retention project."),
+                ("previous-retention", "7"),
+                ("retention-changed-at", "2023-11-14T22:13:20Z")
+            ]),
+            policy.retention_set(&rule_match(Some(&[("retention", "14"), ("previous-retention", "1")])), 7, changed_at())
+        );
+        // Without rule tags only the change is recorded
+        assert_eq!(
+            tags(&[("previous-retention", "never-expire"), ("retention-changed-at", "2023-11-14T22:13:20Z")]),
+            policy.retention_set(&rule_match(None), 0, changed_at())
+        );
+    }
+
+    #[test]
+    fn test_limit_enforced() {
+        let policy = TaggingPolicy {
+            marker: Some(("org:managed-by".to_string(), "log-retention".to_string())),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            tags(&[
+                ("team", "platform"),
+                ("previous-retention", "never-expire"),
+                ("retention-changed-at", "2023-11-14T22:13:20Z"),
+                ("org:managed-by", "log-retention")
+            ]),
+            policy.limit_enforced(Some(&rule_match(Some(&[("team", "platform")]))), 0, changed_at())
+        );
+        // As with `retention_set`, there is no marker without rule tags
+        assert_eq!(
+            tags(&[("previous-retention", "3653"), ("retention-changed-at", "2023-11-14T22:13:20Z")]),
+            policy.limit_enforced(Some(&rule_match(None)), 3653, changed_at())
+        );
+        assert_eq!(
+            tags(&[("previous-retention", "3653"), ("retention-changed-at", "2023-11-14T22:13:20Z")]),
+            policy.limit_enforced(None, 3653, changed_at())
+        );
+    }
+
+    #[test]
+    fn test_without_marker() {
+        let policy = TaggingPolicy::without_marker();
+
+        assert_eq!(
+            tags(&[
+                ("team", "platform"),
+                ("previous-retention", "3653"),
+                ("retention-changed-at", "2023-11-14T22:13:20Z")
+            ]),
+            policy.retention_set(&rule_match(Some(&[("team", "platform")])), 3653, changed_at())
+        );
+        assert_eq!(
+            tags(&[
+                ("team", "platform"),
+                ("previous-retention", "3653"),
+                ("retention-changed-at", "2023-11-14T22:13:20Z")
+            ]),
+            policy.limit_enforced(Some(&rule_match(Some(&[("team", "platform")]))), 3653, changed_at())
+        );
+    }
+
+    #[test]
+    fn test_change_record_tags() {
+        let policy = TaggingPolicy::default();
+
+        assert_eq!(
+            tags(&[("previous-retention", "3653"), ("retention-changed-at", "2023-11-14T22:13:20Z")]),
+            policy.change_record_tags(3653, changed_at())
+        );
+        assert_eq!(
+            Some(&"never-expire".to_string()),
+            policy.change_record_tags(0, changed_at()).get("previous-retention")
+        );
+
+        let policy = TaggingPolicy {
+            previous_retention_key: "org:previous-retention".to_string(),
+            changed_at_key: "org:retention-changed-at".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            tags(&[("org:previous-retention", "3653"), ("org:retention-changed-at", "2023-11-14T22:13:20Z")]),
+            policy.change_record_tags(3653, changed_at())
+        );
+    }
+
+    #[test]
+    fn test_change_record() {
+        let policy = TaggingPolicy::default();
+
+        // Reads back what `change_record_tags` writes
+        assert_eq!(
+            Some(ChangeRecord {
+                previous_retention_in_days: 3653,
+                changed_at: Some(changed_at())
+            }),
+            policy.change_record(Some(&policy.change_record_tags(3653, changed_at())))
+        );
+        assert_eq!(
+            Some(ChangeRecord {
+                previous_retention_in_days: 0,
+                changed_at: None
+            }),
+            policy.change_record(Some(&tags(&[("previous-retention", "never-expire"), ("retention-changed-at", "yesterday")])))
+        );
+        assert_eq!(None, policy.change_record(Some(&tags(&[("previous-retention", "45")]))));
+        assert_eq!(
+            None,
+            policy.change_record(Some(&tags(&[("retention", "Set by AWS Default Log Retention project.")])))
+        );
+        assert_eq!(None, policy.change_record(None));
+
+        let policy = TaggingPolicy {
+            previous_retention_key: "org:previous-retention".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(None, policy.change_record(Some(&tags(&[("previous-retention", "3653")]))));
+        assert!(policy.change_record(Some(&tags(&[("org:previous-retention", "3653")]))).is_some());
+    }
+
+    #[test]
+    fn test_rolled_back() {
+        let policy = TaggingPolicy::default();
+        let rolled_back = policy.rolled_back(changed_at());
+
+        assert_eq!(tags(&[("retention-rolled-back-at", "2023-11-14T22:13:20Z")]), rolled_back);
+        assert_eq!(
+            Some(("retention-rolled-back-at", "2023-11-14T22:13:20Z")),
+            policy.rolled_back_tag(Some(&rolled_back))
+        );
+        assert_eq!(None, policy.rolled_back_tag(Some(&tags(&[("retention", "14")]))));
+        assert_eq!(None, policy.rolled_back_tag(None));
+    }
+
+    #[test]
+    fn test_is_marked() {
+        let policy = TaggingPolicy::default();
+
+        assert!(policy.is_marked(Some(&tags(&[("retention", "Set by AWS Default Log Retention project.")]))));
+        assert!(!policy.is_marked(Some(&tags(&[("retention", "14")]))));
+        assert!(!policy.is_marked(None));
+        assert!(!TaggingPolicy::without_marker().is_marked(Some(&tags(&[("retention", "Set by AWS Default Log Retention project.")]))));
+    }
+}
@@ -33,6 +33,22 @@ locals {
       function_name       = aws_lambda_function.global_log_retention.function_name
     }
   }
+
+  # Failures the global Lambda counts in its own metrics rather than failing the invocation, so the `Errors` alarms above never see them
+  metric_alarms = {
+    global-retention-errored = {
+      description   = "The ${aws_lambda_function.global_log_retention.function_name} function failed to set retention on some log groups! See the `errored` outcomes in its report."
+      period        = 300
+      metric_name   = "Errored"
+      function_name = aws_lambda_function.global_log_retention.function_name
+    }
+    global-retention-failed-targets = {
+      description   = "The ${aws_lambda_function.global_log_retention.function_name} function could not sweep some accounts or regions! See `failed` in its response."
+      period        = 300
+      metric_name   = "FailedTargets"
+      function_name = aws_lambda_function.global_log_retention.function_name
+    }
+  }
 }
 
 resource "aws_cloudwatch_metric_alarm" "alarm" {
@@ -56,3 +72,51 @@ resource "aws_cloudwatch_metric_alarm" "alarm" {
 
   tags = var.tags
 }
+
+# Sums the metric across every other dimension, e.g. `Account`, `Region` and `Mode`, so that each target is covered
+resource "aws_cloudwatch_metric_alarm" "metric_alarm" {
+  for_each            = local.enable_alarms ? local.metric_alarms : {}
+  alarm_name          = "${var.name}-${each.key}"
+  alarm_description   = each.value.description
+  comparison_operator = "GreaterThanThreshold"
+  threshold           = 0
+  evaluation_periods  = 1
+  treat_missing_data  = "notBreaching"
+  actions_enabled     = true
+
+  metric_query {
+    id          = "failures"
+    expression  = "SELECT SUM(${each.value.metric_name}) FROM \"${var.metric_namespace}\" WHERE function = '${each.value.function_name}'"
+    period      = each.value.period
+    return_data = true
+  }
+
+  alarm_actions             = [local.sns_topic_arn]
+  ok_actions                = []
+  insufficient_data_actions = []
+
+  tags = var.tags
+}
+
+# With `event_queue_batch_size`, failed events are returned as `batchItemFailures` rather than Lambda `Errors`, so they are only seen here
+resource "aws_cloudwatch_metric_alarm" "dead_letter" {
+  count               = local.enable_alarms && var.event_queue_batch_size != null ? 1 : 0
+  alarm_name          = "${var.name}-log-group-creation-dead-letter"
+  alarm_description   = "Log group creation events failed ${var.event_queue_max_receive_count} times and were moved to ${aws_sqs_queue.log_group_creation_dead_letter[0].name}! Those log groups did not get default retention."
+  comparison_operator = "GreaterThanThreshold"
+  threshold           = 0
+  evaluation_periods  = 1
+  metric_name         = "ApproximateNumberOfMessagesVisible"
+  namespace           = "AWS/SQS"
+  period              = 300
+  statistic           = "Maximum"
+  actions_enabled     = true
+
+  alarm_actions             = [local.sns_topic_arn]
+  ok_actions                = []
+  insufficient_data_actions = []
+
+  dimensions = { QueueName = aws_sqs_queue.log_group_creation_dead_letter[0].name }
+
+  tags = var.tags
+}
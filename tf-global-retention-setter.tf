@@ -20,11 +20,38 @@ resource "aws_lambda_function" "global_log_retention" {
 
   environment {
     variables = {
-      log_retention_in_days = var.log_retention_in_days
-      log_group_tags        = local.log_group_tags_json
-      metric_namespace      = var.metric_namespace
-      RUST_BACKTRACE        = 1
-      RUST_LOG              = "warn,global_retention_setter=${var.log_level}" # https://docs.rs/env_logger/latest/env_logger/
+      log_retention_in_days        = var.log_retention_in_days
+      log_retention_rules          = local.log_retention_rules_json
+      invalid_retention_strategy   = var.invalid_retention_strategy
+      max_retention_in_days        = local.max_retention_in_days
+      max_retention_exemptions     = jsonencode(var.max_retention_exemptions)
+      min_retention_in_days        = local.min_retention_in_days
+      min_retention_prefixes       = jsonencode(var.min_retention_prefixes)
+      log_group_tags               = local.log_group_tags_json
+      log_group_exclusions         = local.log_group_exclusions_json
+      retention_tag_key            = var.retention_tag_key
+      additional_opt_out_tag_keys  = jsonencode(var.additional_opt_out_tag_keys)
+      marker_tag_key               = local.marker_tag_key
+      marker_tag_value             = var.marker_tag_value
+      marker_tag_enabled           = var.marker_tag_enabled
+      previous_retention_tag_key   = var.previous_retention_tag_key
+      retention_changed_at_tag_key = var.retention_changed_at_tag_key
+      rolled_back_tag_key          = var.rolled_back_tag_key
+      dry_run                      = var.dry_run
+      policy_parameter_name        = local.policy_parameter_name
+      policy_parameter_ttl_seconds = var.policy_parameter_ttl_seconds
+      concurrency                  = var.global_concurrency
+      report_inline_limit          = var.report_inline_limit
+      report_bucket                = local.report_bucket
+      report_prefix                = var.report_prefix
+      report_format                = var.report_format
+      regions                      = jsonencode(var.regions)
+      accounts                     = jsonencode(var.accounts)
+      cross_account_role_name      = local.cross_account_role_name
+      metric_namespace             = var.metric_namespace
+      aws_partition                = data.aws_partition.current.partition
+      RUST_BACKTRACE               = 1
+      RUST_LOG                     = "warn,global_retention_setter=${var.log_level},terraform_aws_default_log_retention=${var.log_level}" # https://docs.rs/env_logger/latest/env_logger/
     }
   }
 
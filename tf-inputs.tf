@@ -56,10 +56,160 @@ variable "log_retention_in_days" {
   description = "Default number of days to set on new log groups. Must be a valid option that CloudWatch Logs support: https://docs.aws.amazon.com/AmazonCloudWatchLogs/latest/APIReference/API_PutRetentionPolicy.html#API_PutRetentionPolicy_RequestParameters"
 }
 
+variable "invalid_retention_strategy" {
+  type        = string
+  default     = "reject"
+  description = "What to do when `log_retention_in_days` or a rule's `retention_in_days` is not a value CloudWatch Logs accepts. `reject` fails at Lambda startup; `round_up`, `round_down` and `nearest` pick an allowed value instead."
+
+  validation {
+    condition     = contains(["reject", "round_up", "round_down", "nearest"], var.invalid_retention_strategy)
+    error_message = "Must be one of reject, round_up, round_down, nearest."
+  }
+}
+
+variable "log_retention_rules" {
+  type = list(object({
+    name              = string
+    glob              = optional(string)
+    regex             = optional(string)
+    retention_in_days = number
+    tags              = optional(map(string))
+  }))
+  default     = null
+  description = "Ordered list of rules matched against the log group name. The first matching rule wins; groups matching no rule get `log_retention_in_days` and `log_group_tags`. Each rule must set exactly one of `glob` or `regex`. If a rule omits `tags`, `log_group_tags` is used."
+}
+
+variable "retention_tag_key" {
+  type        = string
+  default     = "retention"
+  description = "Tag key which keeps a log group at the retention it holds (e.g. `14`), or opts the group out with any other value."
+}
+
+variable "additional_opt_out_tag_keys" {
+  type        = list(string)
+  default     = []
+  description = "More tag keys which opt a log group out, whatever their value."
+}
+
+variable "marker_tag_key" {
+  type        = string
+  default     = null
+  description = "Tag key this module marks log groups it sets retention on with. Defaults to `retention_tag_key`, so marked groups are not changed again."
+}
+
+variable "marker_tag_value" {
+  type        = string
+  default     = "Set by AWS Default Log Retention project."
+  description = "Value of the marker tag."
+}
+
+variable "marker_tag_enabled" {
+  type        = bool
+  default     = true
+  description = "Set to false to stop tagging log groups with the marker tag. Tags from `log_group_tags`, rules and retention limits are still applied."
+}
+
+variable "previous_retention_tag_key" {
+  type        = string
+  default     = "previous-retention"
+  description = "Tag key recording the retention a log group had before this module changed it, read back by `--rollback`."
+}
+
+variable "retention_changed_at_tag_key" {
+  type        = string
+  default     = "retention-changed-at"
+  description = "Tag key recording when this module changed a log group's retention, read back by `--rollback --changed-since`."
+}
+
+variable "rolled_back_tag_key" {
+  type        = string
+  default     = "retention-rolled-back-at"
+  description = "Tag key `--rollback` puts on the log groups it restores, holding the time of the rollback. Neither Lambda changes a group with this tag."
+}
+
 variable "log_group_tags" {
   type        = map(string)
   default     = null
-  description = "Set of tags to put on all log groups when retention is set. If not set, no tags will be added. If set, a `retention` tag will automatically be added to this list."
+  description = "Set of tags to put on all log groups when retention is set. If not set, no tags will be added. If set, the marker tag will automatically be added to this list unless `marker_tag_enabled` is false."
+}
+
+variable "max_retention_in_days" {
+  type        = number
+  default     = null
+  description = "Optional ceiling on retention. Groups with a longer retention, or none at all, are reduced to this value and tagged with their `previous-retention`. Subject to `invalid_retention_strategy`."
+}
+
+variable "max_retention_exemptions" {
+  type        = list(string)
+  default     = []
+  description = "Log group names or globs (e.g. `/audit/*`) which are allowed to exceed `max_retention_in_days`."
+}
+
+variable "log_group_exclusions" {
+  type = object({
+    prefixes = optional(list(string))
+    regexes  = optional(list(string))
+    names    = optional(list(string))
+  })
+  default     = null
+  description = "Log groups neither Lambda touches: names starting with one of `prefixes`, matching one of `regexes`, or listed in `names` by name or ARN. Excluded groups are counted in the `Excluded` metric."
+}
+
+variable "min_retention_in_days" {
+  type        = number
+  default     = null
+  description = "Optional compliance floor on retention. Groups with a shorter retention are raised to this value and tagged with their `previous-retention`. Groups tagged with a non-numeric `retention` are left alone. Subject to `invalid_retention_strategy`."
+}
+
+variable "min_retention_prefixes" {
+  type        = list(string)
+  default     = []
+  description = "Limit `min_retention_in_days` to log groups whose name starts with one of these prefixes. Empty applies the floor to all groups."
+}
+
+variable "event_queue_batch_size" {
+  type        = number
+  default     = null
+  description = "Set to deliver log group creation events to the event-driven Lambda through an SQS queue, in batches of up to this many events. Smooths out bursts of log group creation; only events which fail with an error are retried. Null invokes the Lambda directly from EventBridge."
+
+  validation {
+    condition     = var.event_queue_batch_size == null ? true : var.event_queue_batch_size >= 1 && var.event_queue_batch_size <= 10
+    error_message = "Must be between 1 and 10."
+  }
+}
+
+variable "event_queue_max_receive_count" {
+  type        = number
+  default     = 5
+  description = "With `event_queue_batch_size`, how many times an event is retried before it is moved to the `<name>-log-group-creation-dead-letter` queue."
+
+  validation {
+    condition     = var.event_queue_max_receive_count >= 1 && var.event_queue_max_receive_count <= 1000
+    error_message = "Must be between 1 and 1000."
+  }
+}
+
+variable "dry_run" {
+  type        = bool
+  default     = false
+  description = "Set to true to have both Lambdas log and return what they would change without setting retention or tags. A single invocation can also be made a dry run by passing `\"dryRun\": true` in its payload."
+}
+
+variable "policy_parameter_name" {
+  type        = string
+  default     = null
+  description = "Name of an SSM parameter holding a JSON object of settings (e.g. `{\"log_retention_in_days\": 14, \"log_group_tags\": {\"team\": \"platform\"}}`) which take the place of these inputs. Both Lambdas re-read it every `policy_parameter_ttl_seconds` and pick up new versions without a Terraform apply."
+}
+
+variable "policy_parameter_ttl_seconds" {
+  type        = number
+  default     = 300
+  description = "How long a warm Lambda uses the settings read from `policy_parameter_name` before checking it for a new version."
+
+  validation {
+    condition     = var.policy_parameter_ttl_seconds > 0
+    error_message = "Must be greater than 0."
+  }
 }
 
 variable "set_on_all_existing_groups" {
@@ -68,6 +218,69 @@ variable "set_on_all_existing_groups" {
   description = "Set to false to disable running a bit of code which will set retention on all existing groups."
 }
 
+variable "global_concurrency" {
+  type        = number
+  default     = 10
+  description = "Number of log groups the global log retention Lambda works on at once. Calls are also rate limited to stay under the default CloudWatch Logs API quotas."
+
+  validation {
+    condition     = var.global_concurrency >= 1
+    error_message = "Must be at least 1."
+  }
+}
+
+variable "report_inline_limit" {
+  type        = number
+  default     = 100
+  description = "Largest per-log-group report the global log retention Lambda returns in its response. Larger reports are written to the Lambda's logs instead."
+
+  validation {
+    condition     = var.report_inline_limit >= 0
+    error_message = "Must not be negative."
+  }
+}
+
+variable "report_bucket" {
+  type        = string
+  default     = null
+  description = "S3 bucket to upload every global sweep's per-log-group report to, keyed by account, region and time. Reports are not uploaded if null."
+}
+
+variable "report_prefix" {
+  type        = string
+  default     = ""
+  description = "Prefix of report keys in `report_bucket`, e.g. `log-retention/`."
+}
+
+variable "report_format" {
+  type        = string
+  default     = "jsonl"
+  description = "Format of reports uploaded to `report_bucket`: `jsonl` (JSON Lines) or `csv`."
+
+  validation {
+    condition     = contains(["jsonl", "csv"], var.report_format)
+    error_message = "Must be one of jsonl, csv."
+  }
+}
+
+variable "regions" {
+  type        = list(string)
+  default     = []
+  description = "Regions for the global log retention Lambda to sweep, e.g. `[\"us-east-1\", \"us-west-2\"]`. Empty sweeps only the region the module is deployed to. New log groups in other regions are only picked up by the scheduled sweep."
+}
+
+variable "accounts" {
+  type        = list(string)
+  default     = []
+  description = "Other AWS account IDs for the global log retention Lambda to sweep by assuming `cross_account_role_name` in each. Empty sweeps only the account the module is deployed to."
+}
+
+variable "cross_account_role_name" {
+  type        = string
+  default     = null
+  description = "Name of the role the global log retention Lambda assumes in each of `accounts`. The role must trust this module's Lambda role and allow the same CloudWatch Logs actions. Required when `accounts` is set."
+}
+
 variable "global_log_retention_run_period" {
   type        = number
   default     = 60 * 6
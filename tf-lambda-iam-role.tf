@@ -38,6 +38,38 @@ data "aws_iam_policy_document" "log_retention" {
     }
   }
 
+  dynamic "statement" {
+    for_each = var.cross_account_role_name == null ? [] : [var.cross_account_role_name]
+    content {
+      actions   = ["sts:AssumeRole"]
+      resources = [for account in var.accounts : "arn:${data.aws_partition.current.partition}:iam::${account}:role/${statement.value}"]
+    }
+  }
+
+  dynamic "statement" {
+    for_each = var.policy_parameter_name == null ? [] : [trimprefix(var.policy_parameter_name, "/")]
+    content {
+      actions   = ["ssm:GetParameter"]
+      resources = ["arn:${data.aws_partition.current.partition}:ssm:${data.aws_region.current.name}:${data.aws_caller_identity.current.account_id}:parameter/${statement.value}"]
+    }
+  }
+
+  dynamic "statement" {
+    for_each = var.report_bucket == null ? [] : [var.report_bucket]
+    content {
+      actions   = ["s3:PutObject"]
+      resources = ["arn:${data.aws_partition.current.partition}:s3:::${statement.value}/${var.report_prefix}*"]
+    }
+  }
+
+  dynamic "statement" {
+    for_each = var.event_queue_batch_size == null ? [] : ["make this block once"]
+    content {
+      actions   = ["sqs:ReceiveMessage", "sqs:DeleteMessage", "sqs:GetQueueAttributes"]
+      resources = [aws_sqs_queue.log_group_creation[0].arn]
+    }
+  }
+
   statement {
     actions   = ["ec2:*NetworkInterface*"]
     resources = ["*"]
@@ -12,6 +12,14 @@ locals {
   global_log_retention_lambda_name = "${var.name}-global-log-retention-setter"
   iam_role_name                    = "${local.log_retention_lambda_name}${var.iam_role_suffix}"
   log_group_tags_json              = var.log_group_tags == null ? "" : jsonencode(var.log_group_tags) # Null causes JSON parse error in Lambda
+  log_retention_rules_json         = var.log_retention_rules == null ? "" : jsonencode(var.log_retention_rules)
+  log_group_exclusions_json        = var.log_group_exclusions == null ? "" : jsonencode(var.log_group_exclusions)
+  max_retention_in_days            = var.max_retention_in_days == null ? "" : tostring(var.max_retention_in_days) # Empty means no maximum
+  min_retention_in_days            = var.min_retention_in_days == null ? "" : tostring(var.min_retention_in_days) # Empty means no minimum
+  cross_account_role_name          = var.cross_account_role_name == null ? "" : var.cross_account_role_name
+  report_bucket                    = var.report_bucket == null ? "" : var.report_bucket
+  marker_tag_key                   = var.marker_tag_key == null ? "" : var.marker_tag_key
+  policy_parameter_name            = var.policy_parameter_name == null ? "" : var.policy_parameter_name
 
   runtime       = "provided.al2023"
   architectures = ["arm64"]
@@ -20,12 +20,30 @@ resource "aws_lambda_function" "log_retention" {
 
   environment {
     variables = {
-      log_retention_in_days = var.log_retention_in_days
-      log_group_tags        = local.log_group_tags_json
-      metric_namespace      = var.metric_namespace
-      aws_partition         = data.aws_partition.current.partition
-      RUST_BACKTRACE        = 1
-      RUST_LOG              = "warn,terraform_aws_default_log_retention=${var.log_level}" # https://docs.rs/env_logger/latest/env_logger/
+      log_retention_in_days        = var.log_retention_in_days
+      log_retention_rules          = local.log_retention_rules_json
+      invalid_retention_strategy   = var.invalid_retention_strategy
+      max_retention_in_days        = local.max_retention_in_days
+      max_retention_exemptions     = jsonencode(var.max_retention_exemptions)
+      min_retention_in_days        = local.min_retention_in_days
+      min_retention_prefixes       = jsonencode(var.min_retention_prefixes)
+      log_group_tags               = local.log_group_tags_json
+      log_group_exclusions         = local.log_group_exclusions_json
+      retention_tag_key            = var.retention_tag_key
+      additional_opt_out_tag_keys  = jsonencode(var.additional_opt_out_tag_keys)
+      marker_tag_key               = local.marker_tag_key
+      marker_tag_value             = var.marker_tag_value
+      marker_tag_enabled           = var.marker_tag_enabled
+      previous_retention_tag_key   = var.previous_retention_tag_key
+      retention_changed_at_tag_key = var.retention_changed_at_tag_key
+      rolled_back_tag_key          = var.rolled_back_tag_key
+      dry_run                      = var.dry_run
+      policy_parameter_name        = local.policy_parameter_name
+      policy_parameter_ttl_seconds = var.policy_parameter_ttl_seconds
+      metric_namespace             = var.metric_namespace
+      aws_partition                = data.aws_partition.current.partition
+      RUST_BACKTRACE               = 1
+      RUST_LOG                     = "warn,terraform_aws_default_log_retention=${var.log_level}" # https://docs.rs/env_logger/latest/env_logger/
     }
   }
 
@@ -43,6 +61,7 @@ resource "aws_lambda_function" "log_retention" {
 resource "aws_cloudwatch_event_rule" "log_group_creation" {
   name = "${var.name}-log-group-creation"
 
+  # Changes the module's own role makes (e.g. the global Lambda's PutRetentionPolicy and TagResource) are left out, so they do not invoke the event Lambda again
   event_pattern = <<PATTERN
 {
   "source": [
@@ -55,8 +74,31 @@ resource "aws_cloudwatch_event_rule" "log_group_creation" {
     "eventSource": [
       "logs.amazonaws.com"
     ],
-    "eventName": [
-      "CreateLogGroup"
+    "$or": [
+      {
+        "eventName": [
+          "CreateLogGroup"
+        ]
+      },
+      {
+        "eventName": [
+          "PutRetentionPolicy",
+          "DeleteRetentionPolicy",
+          "TagResource",
+          "UntagResource",
+          "TagLogGroup",
+          "UntagLogGroup"
+        ],
+        "userIdentity": {
+          "arn": [
+            {
+              "anything-but": {
+                "prefix": "arn:${data.aws_partition.current.partition}:sts::${data.aws_caller_identity.current.account_id}:assumed-role/${aws_iam_role.log_retention.name}/"
+              }
+            }
+          ]
+        }
+      }
     ]
   }
 }
@@ -67,7 +109,62 @@ PATTERN
 
 resource "aws_cloudwatch_event_target" "log_group_creation" {
   rule = aws_cloudwatch_event_rule.log_group_creation.name
-  arn  = aws_lambda_function.log_retention.arn
+  arn  = var.event_queue_batch_size == null ? aws_lambda_function.log_retention.arn : aws_sqs_queue.log_group_creation[0].arn
+}
+
+resource "aws_sqs_queue" "log_group_creation" {
+  count                      = var.event_queue_batch_size == null ? 0 : 1
+  name                       = "${var.name}-log-group-creation"
+  visibility_timeout_seconds = 6 * 60 # Six times the Lambda's timeout, as recommended for SQS event sources
+  sqs_managed_sse_enabled    = true
+  redrive_policy = jsonencode({
+    deadLetterTargetArn = aws_sqs_queue.log_group_creation_dead_letter[0].arn
+    maxReceiveCount     = var.event_queue_max_receive_count
+  })
+  tags = var.tags
+}
+
+# Events which still fail after `event_queue_max_receive_count` attempts are kept here rather than retried forever
+resource "aws_sqs_queue" "log_group_creation_dead_letter" {
+  count                     = var.event_queue_batch_size == null ? 0 : 1
+  name                      = "${var.name}-log-group-creation-dead-letter"
+  message_retention_seconds = 14 * 24 * 60 * 60 # The longest SQS allows
+  sqs_managed_sse_enabled   = true
+  tags                      = var.tags
+}
+
+resource "aws_sqs_queue_policy" "log_group_creation" {
+  count     = var.event_queue_batch_size == null ? 0 : 1
+  queue_url = aws_sqs_queue.log_group_creation[0].id
+  policy    = data.aws_iam_policy_document.log_group_creation_queue[0].json
+}
+
+data "aws_iam_policy_document" "log_group_creation_queue" {
+  count = var.event_queue_batch_size == null ? 0 : 1
+
+  statement {
+    actions   = ["sqs:SendMessage"]
+    resources = [aws_sqs_queue.log_group_creation[0].arn]
+
+    principals {
+      type        = "Service"
+      identifiers = ["events.amazonaws.com"]
+    }
+
+    condition {
+      test     = "ArnEquals"
+      variable = "aws:SourceArn"
+      values   = [aws_cloudwatch_event_rule.log_group_creation.arn]
+    }
+  }
+}
+
+resource "aws_lambda_event_source_mapping" "log_group_creation" {
+  count                   = var.event_queue_batch_size == null ? 0 : 1
+  event_source_arn        = aws_sqs_queue.log_group_creation[0].arn
+  function_name           = aws_lambda_function.log_retention.arn
+  batch_size              = var.event_queue_batch_size
+  function_response_types = ["ReportBatchItemFailures"] # Only records which hit an error are retried
 }
 
 resource "aws_lambda_permission" "log_retention" {